@@ -10,7 +10,10 @@ fn main(){
         if sc.score == 100.0 {
             println!("{} has score 100", sc.title);
             let sub_list = client.get_submission_list(&sc.name).unwrap();
-            let best_sub = sub_list.get_fastest_high(&client).unwrap();
+            let best_sub = sub_list
+                .get_fastest_high(&client, Metric::MaxTime)
+                .unwrap()
+                .unwrap();
             let files = &best_sub.files;
             if files.len() == 1 { // if it is not an output-only
                 let mut submitted: bool = false;
@@ -18,17 +21,18 @@ fn main(){
                 while !submitted { // because cmsocial has a limit to submission rate
                     print!(".");
                     if client
-                        .submit_normal(
+                        .submit_file(
                             &sc.name,
                             &client.get_file(&files[0]).unwrap(),
-                            files[0].name.split(".").collect::<Vec<&str>>().last().unwrap(),
+                            &files[0].name,
+                            None,
                         )
                         .is_ok()
                     {
                         submitted = true;
                     }
                 }
-                println!("");
+                println!();
             }
         }
     }
@@ -0,0 +1,214 @@
+//! Analysis of the results of a submission: subtasks, verdicts and distance from the limits
+
+use crate::{DetailedSubmission, Testcase};
+use std::collections::BTreeMap;
+
+/// fraction of a limit above which a testcase is considered near the limit
+pub const NEAR_LIMIT: f64 = 0.8;
+
+/// Results of a submission on a subtask
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SubtaskBreakdown {
+    /// index of the subtask, its position if the server does not give it
+    pub idx: usize,
+    pub score: f64,
+    pub max_score: f64,
+    /// number of testcases for each verdict, like `Output is correct`
+    pub verdicts: BTreeMap<String, usize>,
+    /// maximum execution time among the testcases
+    pub max_time: f64,
+    /// maximum memory used among the testcases, in bytes
+    pub max_memory: u64,
+}
+impl SubtaskBreakdown {
+    /// whether the subtask got its full score
+    pub fn passed(&self) -> bool {
+        self.score >= self.max_score
+    }
+}
+
+/// A testcase with the index of its subtask
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SubtaskTestcase {
+    pub subtask: usize,
+    pub testcase: Testcase,
+}
+
+/// Result of `DetailedSubmission::analyze`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Analysis {
+    pub subtasks: Vec<SubtaskBreakdown>,
+    /// number of testcases for each verdict, over all subtasks
+    pub verdicts: BTreeMap<String, usize>,
+    /// slowest testcases, slowest first
+    pub slowest: Vec<SubtaskTestcase>,
+    /// time limit minus the maximum execution time, in seconds
+    pub time_headroom: Option<f64>,
+    /// memory limit minus the maximum memory used, in bytes
+    pub memory_headroom: Option<i64>,
+    /// testcases within the time limit, but using more than `NEAR_LIMIT` of it
+    pub near_time_limit: Vec<SubtaskTestcase>,
+    /// testcases within the memory limit, but using more than `NEAR_LIMIT` of it
+    pub near_memory_limit: Vec<SubtaskTestcase>,
+}
+
+impl DetailedSubmission {
+    fn testcases(&self) -> impl Iterator<Item = SubtaskTestcase> + '_ {
+        self.score_details
+            .iter()
+            .flatten()
+            .enumerate()
+            .flat_map(|(i, st)| {
+                st.testcases.iter().map(move |tc| SubtaskTestcase {
+                    subtask: st.idx.unwrap_or(i),
+                    testcase: tc.clone(),
+                })
+            })
+    }
+
+    /// get the results on each subtask, empty if the submission has not been evaluated
+    pub fn subtask_breakdown(&self) -> Vec<SubtaskBreakdown> {
+        self.score_details
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(i, st)| {
+                let mut res = SubtaskBreakdown {
+                    idx: st.idx.unwrap_or(i),
+                    score: st.score,
+                    max_score: st.max_score,
+                    verdicts: BTreeMap::new(),
+                    max_time: 0.0,
+                    max_memory: 0,
+                };
+                for tc in &st.testcases {
+                    *res.verdicts.entry(tc.text.clone()).or_insert(0) += 1;
+                    res.max_time = res.max_time.max(tc.time);
+                    res.max_memory = res.max_memory.max(tc.memory);
+                }
+                res
+            })
+            .collect()
+    }
+
+    /// get the number of testcases for each verdict
+    pub fn verdicts(&self) -> BTreeMap<String, usize> {
+        let mut res = BTreeMap::new();
+        for tc in self.testcases() {
+            *res.entry(tc.testcase.text).or_insert(0) += 1;
+        }
+        res
+    }
+
+    /// get the `n` slowest testcases, slowest first
+    pub fn slowest_testcases(&self, n: usize) -> Vec<SubtaskTestcase> {
+        let mut res: Vec<SubtaskTestcase> = self.testcases().collect();
+        res.sort_by(|a, b| b.testcase.time.partial_cmp(&a.testcase.time).unwrap());
+        res.truncate(n);
+        res
+    }
+
+    /// analyze the results under the limits of the task,
+    /// `time_limit` in seconds and `memory_limit` in MiB like in `DetailedTask`
+    pub fn analyze(
+        &self,
+        time_limit: Option<f64>,
+        memory_limit: Option<u16>,
+        slowest: usize,
+    ) -> Analysis {
+        let memory_limit = memory_limit.map(|m| m as u64 * 1024 * 1024);
+        let near = |value: f64, limit: Option<f64>| {
+            limit.is_some_and(|l| value >= l * NEAR_LIMIT && value <= l)
+        };
+        Analysis {
+            subtasks: self.subtask_breakdown(),
+            verdicts: self.verdicts(),
+            slowest: self.slowest_testcases(slowest),
+            time_headroom: time_limit.zip(self.get_time()).map(|(l, t)| l - t),
+            memory_headroom: memory_limit
+                .zip(self.get_memory())
+                .map(|(l, m)| l as i64 - m as i64),
+            near_time_limit: self
+                .testcases()
+                .filter(|tc| near(tc.testcase.time, time_limit))
+                .collect(),
+            near_memory_limit: self
+                .testcases()
+                .filter(|tc| near(tc.testcase.memory as f64, memory_limit.map(|m| m as f64)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScoreDetail;
+
+    #[test]
+    fn analyze() {
+        let testcase = |text: &str, time, memory| Testcase {
+            text: String::from(text),
+            outcome: String::from(if text == "Output is correct" {
+                "Correct"
+            } else {
+                "Not correct"
+            }),
+            time,
+            idx: None,
+            memory,
+        };
+        let sub = DetailedSubmission {
+            files: Vec::new(),
+            compilation_outcome: Some(String::from("ok")),
+            task_id: 1,
+            language: None,
+            score_details: Some(vec![
+                ScoreDetail {
+                    testcases: vec![
+                        testcase("Output is correct", 0.1, 1 << 20),
+                        testcase("Output is correct", 0.9, 60 << 20),
+                    ],
+                    score: 30.0,
+                    max_score: 30.0,
+                    idx: Some(1),
+                },
+                ScoreDetail {
+                    testcases: vec![
+                        testcase("Output is correct", 0.5, 2 << 20),
+                        testcase("Execution timed out", 1.1, 2 << 20),
+                    ],
+                    score: 0.0,
+                    max_score: 70.0,
+                    idx: Some(2),
+                },
+            ]),
+            timestamp: 0.0,
+            compilation_stderr: None,
+            compilation_time: None,
+            evaluation_outcome: Some(String::from("ok")),
+            score: Some(30.0),
+            compilation_stdout: None,
+            success: 1,
+            id: 1,
+            compilation_memory: None,
+            extra: Default::default(),
+        };
+        let a = sub.analyze(Some(1.0), Some(64), 2);
+        assert!(a.subtasks[0].passed());
+        assert!(!a.subtasks[1].passed());
+        assert_eq!(a.subtasks[1].verdicts["Execution timed out"], 1);
+        assert_eq!(a.verdicts["Output is correct"], 3);
+        assert_eq!(a.slowest.len(), 2);
+        assert_eq!(a.slowest[0].subtask, 2);
+        assert_eq!(a.slowest[1].testcase.time, 0.9);
+        assert!(a.time_headroom.unwrap() < 0.0);
+        assert_eq!(a.memory_headroom, Some(4 << 20));
+        assert_eq!(a.near_time_limit.len(), 1);
+        assert_eq!(a.near_time_limit[0].subtask, 1);
+        assert_eq!(a.near_memory_limit.len(), 1);
+        let a = sub.analyze(None, None, 10);
+        assert_eq!(a.slowest.len(), 4);
+        assert!(a.time_headroom.is_none() && a.near_time_limit.is_empty());
+    }
+}
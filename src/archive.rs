@@ -0,0 +1,211 @@
+//! Archive submissions into a git repository
+//!
+//! Every submission becomes a commit dated like the submission, changing the files
+//! `<task name>/<file name>` and with a message like
+//! ```text
+//! ois_luck: 100 (C++17 / g++)
+//!
+//! Submission-Id: 123456
+//! Score: 100
+//! Language: C++17 / g++
+//! ```
+//! Archiving again only adds the submissions not already in the repository.
+//!
+//! Functions here return `Err(1)` also if a file cannot be written or a git command fails.
+
+use crate::language::Language;
+use crate::workspace::sanitize;
+use crate::{Client, Submission};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// trailer identifying the submission of a commit
+const ID_TRAILER: &str = "Submission-Id: ";
+
+/// Options for `Client::archive_submissions`
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    /// only archive the last submission with the highest score of each task
+    pub best_only: bool,
+}
+
+/// A git repository of submissions
+pub struct Archive {
+    pub dir: PathBuf,
+    /// name and email used for commits
+    pub author: (String, String),
+}
+impl Archive {
+    /// open the repository in `dir`, creating it if needed
+    pub fn open<P: AsRef<Path>>(dir: P, author: (String, String)) -> Result<Self, u8> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir).map_err(|_| 1)?;
+        let res = Archive { dir, author };
+        if !res.dir.join(".git").exists() {
+            res.git(&["init", "-q"], None)?;
+        }
+        Ok(res)
+    }
+
+    fn git(&self, args: &[&str], date: Option<f64>) -> Result<String, u8> {
+        let mut cmd = Command::new("git");
+        cmd.current_dir(&self.dir)
+            .args(args)
+            .env("GIT_AUTHOR_NAME", &self.author.0)
+            .env("GIT_AUTHOR_EMAIL", &self.author.1)
+            .env("GIT_COMMITTER_NAME", &self.author.0)
+            .env("GIT_COMMITTER_EMAIL", &self.author.1);
+        if let Some(date) = date {
+            let date = format!("@{} +0000", date as i64);
+            cmd.env("GIT_AUTHOR_DATE", &date)
+                .env("GIT_COMMITTER_DATE", &date);
+        }
+        let out = cmd.output().map_err(|_| 1)?;
+        if !out.status.success() {
+            return Err(1);
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+    }
+
+    /// ids of the submissions already in the repository
+    pub fn archived_ids(&self) -> Result<HashSet<usize>, u8> {
+        // an empty repository has no HEAD
+        if self
+            .git(&["rev-parse", "-q", "--verify", "HEAD"], None)
+            .is_err()
+        {
+            return Ok(HashSet::new());
+        }
+        Ok(self
+            .git(&["log", "--format=%B"], None)?
+            .lines()
+            .filter_map(|l| l.strip_prefix(ID_TRAILER))
+            .filter_map(|id| id.trim().parse().ok())
+            .collect())
+    }
+
+    /// commit a submission of `task_name` in `language`, with the contents of its files
+    pub fn commit(
+        &self,
+        task_name: &str,
+        sub: &Submission,
+        language: Option<Language>,
+        files: &[(String, Vec<u8>)],
+    ) -> Result<(), u8> {
+        let task_dir = self.dir.join(task_name);
+        std::fs::create_dir_all(&task_dir).map_err(|_| 1)?;
+        for (name, data) in files {
+            std::fs::write(task_dir.join(name), data).map_err(|_| 1)?;
+        }
+        self.git(&["add", "--all", "--", task_name], None)?;
+        let language = language.map_or("unknown", |l| l.server_name());
+        let score = match (sub.score, &sub.compilation_outcome) {
+            (_, Some(c)) if c == "fail" => String::from("compilation failed"),
+            (Some(score), _) => format!("{}", score),
+            _ => String::from("not evaluated"),
+        };
+        let message = format!(
+            "{}: {} ({})\n\n{}{}\nScore: {}\nLanguage: {}\n",
+            task_name, score, language, ID_TRAILER, sub.id, score, language
+        );
+        self.git(
+            &["commit", "-q", "--allow-empty", "-m", &message],
+            Some(sub.timestamp),
+        )?;
+        Ok(())
+    }
+}
+
+impl Client {
+    /// archive the submissions of the logged user in the git repository in `dir`
+    ///
+    /// returns the number of submissions added
+    pub fn archive_submissions<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        options: &ArchiveOptions,
+    ) -> Result<usize, u8> {
+        let archive = Archive::open(
+            dir,
+            (
+                self.username.clone(),
+                format!("{}@training.olinfo.it", self.username),
+            ),
+        )?;
+        let archived = archive.archived_ids()?;
+        let user = self.get_user(&self.username)?;
+        let mut todo: Vec<(String, Submission)> = Vec::new();
+        for score in user.scores.unwrap_or_default() {
+            let list = self.get_submission_list(&score.name)?;
+            let subs: Vec<Submission> = if options.best_only {
+                list.get_last_high().into_iter().cloned().collect()
+            } else {
+                list.submissions
+            };
+            for sub in subs {
+                if !archived.contains(&sub.id) {
+                    todo.push((sanitize(&score.name), sub));
+                }
+            }
+        }
+        todo.sort_by(|a, b| a.1.timestamp.partial_cmp(&b.1.timestamp).unwrap());
+        for (task_name, sub) in &todo {
+            let mut files = Vec::new();
+            for file in &sub.files {
+                files.push((sanitize(&file.name), self.get_file_bytes(file)?));
+            }
+            // the submission list does not tell versions like C++11 and C++17 apart
+            let language = self.get_submission(sub.id)?.get_language();
+            archive.commit(task_name, sub, language, &files)?;
+        }
+        Ok(todo.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commits() {
+        let dir = std::env::temp_dir().join(format!("cms_tools_archive_{}", std::process::id()));
+        let archive = Archive::open(
+            &dir,
+            (String::from("user"), String::from("user@example.com")),
+        )
+        .unwrap();
+        assert!(archive.archived_ids().unwrap().is_empty());
+        let sub = |id, score| Submission {
+            files: vec![crate::File {
+                name: String::from("luck.cpp"),
+                digest: String::new(),
+            }],
+            compilation_outcome: Some(String::from("ok")),
+            task_id: 1,
+            timestamp: 1500000000.0 + id as f64,
+            evaluation_outcome: Some(String::from("ok")),
+            score: Some(score),
+            id,
+        };
+        let files = vec![(String::from("luck.cpp"), b"int main(){}".to_vec())];
+        archive
+            .commit("ois_luck", &sub(7, 40.0), Some(Language::Cpp17), &files)
+            .unwrap();
+        // same source again, still gets its own commit
+        archive
+            .commit("ois_luck", &sub(8, 40.0), Some(Language::Cpp11), &files)
+            .unwrap();
+        let ids = archive.archived_ids().unwrap();
+        assert_eq!(ids, [7, 8].iter().cloned().collect());
+        let log = archive
+            .git(&["log", "-1", "--format=%at %s"], None)
+            .unwrap();
+        assert_eq!(log.trim(), "1500000008 ois_luck: 40 (C++11 / g++)");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("ois_luck/luck.cpp")).unwrap(),
+            "int main(){}"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,671 @@
+//! `cms`, a command line client for training.olinfo.it
+//!
+//! `cms login` saves the session cookies in `~/.cms_tools.json`, readable only by the user,
+//! the password is never saved. The credentials can also be given with the `CMS_USERNAME`
+//! and `CMS_PASSWORD` environment variables, logging in at every command.
+
+use cms_tools::*;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+
+const USAGE: &str = "usage: cms [--json] <command> [args]
+
+commands:
+    login <username>
+    task show <task>
+    task list [--tag <tag>] [--search <text>] [--order newest|easiest|hardest]
+              [--first <n>] [--last <n>]
+    task fetch <task> [--dir <directory>] [--lang <extension>]
+    submit <task> <file> [--force] [--language <language>]
+    test <file> [--dir <task directory>] [--exact]
+    stress <generator> <brute> <file> [--dir <task directory>] [--iterations <n>]
+           [--seed <n>] [--exact]
+    status <submission id> [--task <task>]
+    diff <submission id> <submission id>
+    submissions <task>
+    archive <directory> [--best]
+    stats <task>
+
+--json prints the raw responses as JSON instead of human-readable text
+
+login reads the password from the terminal without echoing it, or from stdin if it is
+not a terminal
+
+with --task, status also shows how close the submission is to the limits of the task
+
+if the file to submit is in a directory created by `task fetch`, it is tested
+on the samples first and not submitted if it fails, unless --force is given
+
+the language of the file to submit is recognized from its extension and content,
+C++ is submitted as C++17 unless --language is one of: c11, cpp11, cpp14, cpp17,
+csharp, go, haskell, java, pascal, php, python2, python3, pypy3, rust";
+
+/// options that do not take a value
+const FLAGS: &[&str] = &["json", "force", "exact", "best"];
+
+/// Command line arguments split into positional ones, `--flag` ones and `--option value` ones
+struct Args {
+    flags: HashSet<String>,
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut res = Args {
+            flags: HashSet::new(),
+            positional: Vec::new(),
+            options: HashMap::new(),
+        };
+        while let Some(arg) = args.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                if FLAGS.contains(&name) {
+                    res.flags.insert(String::from(name));
+                    continue;
+                }
+                match args.next() {
+                    Some(value) => {
+                        res.options.insert(String::from(name), value);
+                    }
+                    None => return Err(format!("missing value for --{}", name)),
+                }
+            } else {
+                res.positional.push(arg);
+            }
+        }
+        Ok(res)
+    }
+    fn positional(&self, i: usize, what: &str) -> Result<&str, String> {
+        self.positional
+            .get(i)
+            .map(|s| s.as_str())
+            .ok_or(format!("missing {}\n\n{}", what, USAGE))
+    }
+    fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|s| s.as_str())
+    }
+    fn number(&self, name: &str, default: usize) -> Result<usize, String> {
+        match self.option(name) {
+            Some(s) => s
+                .parse()
+                .map_err(|_| format!("--{} must be a number", name)),
+            None => Ok(default),
+        }
+    }
+}
+
+/// Saved login, the password is not saved
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Session {
+    username: String,
+    /// cookies set by the login, see `Client::session`
+    session: String,
+}
+
+fn credentials_path() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .unwrap_or_default();
+    PathBuf::from(home).join(".cms_tools.json")
+}
+
+fn load_session() -> Option<Session> {
+    let s = std::fs::read_to_string(credentials_path()).ok()?;
+    serde_json::from_str(&s).ok()
+}
+
+fn save_session(session: &Session) -> Result<(), String> {
+    let path = credentials_path();
+    let s = serde_json::to_string(session).unwrap();
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // files saved by older versions may be readable by others
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+    }
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(s.as_bytes()))
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+/// read a line from stdin, without echoing it if stdin is a terminal
+fn read_password() -> Result<String, String> {
+    let stty = |arg: &str| {
+        std::process::Command::new("stty")
+            .arg(arg)
+            .stdin(std::process::Stdio::inherit())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    };
+    let hidden = cfg!(unix) && stty("-echo");
+    let mut line = String::new();
+    let res = std::io::stdin().read_line(&mut line);
+    if hidden {
+        stty("echo");
+        println!();
+    }
+    res.map_err(|e| e.to_string())?;
+    Ok(String::from(line.trim_end_matches(&['\r', '\n'][..])))
+}
+
+fn error_message(code: u8) -> String {
+    String::from(match code {
+        1 => "request failed, is the server reachable?",
+        2 => "cannot parse the response, are the arguments correct?",
+        3 => "the server refused the request",
+        4 => "cannot recognize the language of the file",
+        _ => "unknown error",
+    })
+}
+
+/// get the language named like `cpp14` in the usage
+fn parse_language(name: &str) -> Result<language::Language, String> {
+    language::LANGUAGES
+        .iter()
+        .cloned()
+        .find(|l| format!("{:?}", l).eq_ignore_ascii_case(name))
+        .ok_or(format!("unknown language {}\n\n{}", name, USAGE))
+}
+
+/// whether there are credentials to log in with
+fn logged_in() -> bool {
+    std::env::var_os("CMS_USERNAME").is_some() && std::env::var_os("CMS_PASSWORD").is_some()
+        || load_session().is_some()
+}
+
+/// make a client, logging in with the saved credentials if `login` is true
+fn client(login: bool) -> Result<Client, String> {
+    if !login {
+        return Ok(Client::new(String::new()));
+    }
+    if let (Ok(username), Ok(password)) =
+        (std::env::var("CMS_USERNAME"), std::env::var("CMS_PASSWORD"))
+    {
+        let mut client = Client::new(username);
+        client
+            .login(&password)
+            .map_err(|e| format!("login failed: {}", error_message(e)))?;
+        return Ok(client);
+    }
+    let session = load_session().ok_or("not logged in, run `cms login <username>` first")?;
+    Client::resume(session.username, &session.session)
+        .map_err(|_| String::from("invalid session, run `cms login <username>` again"))
+}
+
+fn print_json<T: serde::Serialize>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+fn print_submission(sub: &DetailedSubmission) {
+    println!("submission {}", sub.id);
+    println!("submitted:   {}", date::format_timestamp(sub.timestamp));
+    if let Some(language) = &sub.language {
+        println!("language:    {}", language);
+    }
+    let files: Vec<&str> = sub.files.iter().map(|f| f.name.as_str()).collect();
+    println!("files:       {}", files.join(", "));
+    match &sub.compilation_outcome {
+        Some(outcome) => println!("compilation: {}", outcome),
+        None => println!("compilation: pending"),
+    }
+    if sub.compilation_outcome.as_deref() == Some("fail") {
+        if let Some(stderr) = &sub.compilation_stderr {
+            println!("{}", stderr);
+        }
+        return;
+    }
+    match &sub.evaluation_outcome {
+        Some(outcome) => println!("evaluation:  {}", outcome),
+        None => println!("evaluation:  pending"),
+    }
+    if let Some(score) = sub.score {
+        println!("score:       {}", score);
+    }
+    if let (Some(time), Some(memory)) = (sub.get_time(), sub.get_memory()) {
+        println!("max time:    {:.3} s", time);
+        println!("max memory:  {} KiB", memory / 1024);
+    }
+    for (i, st) in sub.score_details.iter().flatten().enumerate() {
+        println!(
+            "subtask {}: {}/{}",
+            st.idx.unwrap_or(i),
+            st.score,
+            st.max_score
+        );
+        for tc in &st.testcases {
+            println!(
+                "    {:>4} {:<40} {:.3} s {:>8} KiB",
+                tc.idx.as_deref().unwrap_or(""),
+                tc.text,
+                tc.time,
+                tc.memory / 1024
+            );
+        }
+    }
+}
+
+fn print_analysis(analysis: &analysis::Analysis) {
+    for (verdict, n) in &analysis.verdicts {
+        println!("{:>4} x {}", n, verdict);
+    }
+    let failed: Vec<String> = analysis
+        .subtasks
+        .iter()
+        .filter(|st| !st.passed())
+        .map(|st| st.idx.to_string())
+        .collect();
+    if !failed.is_empty() {
+        println!("failed subtasks: {}", failed.join(", "));
+    }
+    if let Some(headroom) = analysis.time_headroom {
+        println!("time headroom:   {:.3} s", headroom);
+    }
+    if let Some(headroom) = analysis.memory_headroom {
+        println!("memory headroom: {} KiB", headroom / 1024);
+    }
+    for tc in &analysis.near_time_limit {
+        println!(
+            "near the time limit: subtask {} testcase {} ({:.3} s)",
+            tc.subtask,
+            tc.testcase.idx.as_deref().unwrap_or("?"),
+            tc.testcase.time
+        );
+    }
+    for tc in &analysis.near_memory_limit {
+        println!(
+            "near the memory limit: subtask {} testcase {} ({} KiB)",
+            tc.subtask,
+            tc.testcase.idx.as_deref().unwrap_or("?"),
+            tc.testcase.memory / 1024
+        );
+    }
+}
+
+/// directory containing `path`, `.` for relative paths without directories
+fn parent_dir(path: &str) -> &std::path::Path {
+    std::path::Path::new(path)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| std::path::Path::new("."))
+}
+
+/// open the task directory given with `--dir`, or the one containing `path`
+fn open_workspace(args: &Args, path: &str) -> Result<workspace::TaskWorkspace, String> {
+    let dir = match args.option("dir") {
+        Some(dir) => std::path::Path::new(dir),
+        None => parent_dir(path),
+    };
+    workspace::TaskWorkspace::open(dir).map_err(|_| {
+        format!(
+            "{} is not a task directory, create it with `cms task fetch`",
+            dir.display()
+        )
+    })
+}
+
+/// run a solution on the samples of a task and print the results
+fn test_solution(
+    ws: &workspace::TaskWorkspace,
+    path: &str,
+    args: &Args,
+) -> Result<runner::RunReport, String> {
+    let report = ws
+        .test_solution(std::path::Path::new(path), &run_options(args))
+        .map_err(|e| match e {
+            4 => format!("unsupported language for {}", path),
+            _ => format!("cannot test {}", path),
+        })?;
+    print_report(ws, &report);
+    Ok(report)
+}
+
+/// options for running the samples, from `--exact`
+fn run_options(args: &Args) -> runner::RunOptions {
+    runner::RunOptions {
+        exact: args.flag("exact"),
+        ..Default::default()
+    }
+}
+
+/// print the compilation errors or the result on every sample
+fn print_report(ws: &workspace::TaskWorkspace, report: &runner::RunReport) {
+    if report.compilation.program.is_none() {
+        println!("compilation failed");
+        println!("{}", report.compilation.stderr);
+        return;
+    }
+    if report.testcases.is_empty() {
+        println!("warning: no samples found in {}", ws.dir.display());
+    }
+    for tc in &report.testcases {
+        println!(
+            "{:<16} {:<12} {:.3} s {:>8} KiB  {}",
+            tc.idx.as_deref().unwrap_or(""),
+            tc.outcome,
+            tc.time,
+            tc.memory / 1024,
+            tc.text
+        );
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    match args.positional(0, "command")? {
+        "login" => {
+            let username = args.positional(1, "username")?;
+            print!("password: ");
+            std::io::stdout().flush().unwrap();
+            let password = read_password()?;
+            let mut client = Client::new(String::from(username));
+            client
+                .login(&password)
+                .map_err(|e| format!("login failed: {}", error_message(e)))?;
+            let session = client
+                .session()
+                .ok_or("login failed: the server did not set a session cookie")?;
+            save_session(&Session {
+                username: String::from(username),
+                session,
+            })?;
+            println!("logged in as {}", username);
+        }
+        "task" => match args.positional(1, "task command")? {
+            "show" => {
+                let task = client(false)?
+                    .get_task(args.positional(2, "task name")?)
+                    .map_err(error_message)?;
+                if args.flag("json") {
+                    print_json(&task);
+                    return Ok(());
+                }
+                println!("{} ({})", task.title, task.name);
+                println!("type:             {}", task.task_type);
+                if let Some(time_limit) = task.time_limit {
+                    println!("time limit:       {} s", time_limit);
+                }
+                if let Some(memory_limit) = task.memory_limit {
+                    println!("memory limit:     {} MiB", memory_limit);
+                }
+                println!("score multiplier: {}", task.score_multiplier);
+                println!("submission:       {}", task.submission_format.join(", "));
+                let tags: Vec<&str> = task.tags.iter().map(|t| t.name.as_str()).collect();
+                println!("tags:             {}", tags.join(", "));
+                for attachment in &task.attachments {
+                    println!("attachment:       {}", attachment.join(" "));
+                }
+            }
+            "list" => {
+                let list = client(false)?
+                    .get_task_list(
+                        args.number("first", 0)?,
+                        args.number("last", 50)?,
+                        args.option("order").unwrap_or("newest"),
+                        args.option("tag"),
+                        args.option("search"),
+                    )
+                    .map_err(error_message)?;
+                if args.flag("json") {
+                    print_json(&list);
+                    return Ok(());
+                }
+                for t in &list.tasks {
+                    println!("{:<24} {:>5.1}  {}", t.name, t.score_multiplier, t.title);
+                }
+                println!("{} of {} tasks", list.tasks.len(), list.num);
+            }
+            "fetch" => {
+                let name = args.positional(2, "task name")?;
+                let options = workspace::WorkspaceOptions {
+                    lang: String::from(args.option("lang").unwrap_or("cpp")),
+                    submissions: logged_in(),
+                };
+                let client = client(options.submissions)?;
+                let report = client
+                    .materialize_task(args.option("dir").unwrap_or("."), name, &options)
+                    .map_err(error_message)?;
+                for path in &report.downloaded {
+                    println!("downloaded {}", path);
+                }
+                for path in &report.created {
+                    println!("created {}", path);
+                }
+                println!("task directory: {}", report.workspace.dir.display());
+            }
+            other => return Err(format!("unknown task command {}\n\n{}", other, USAGE)),
+        },
+        "submit" => {
+            let task_name = args.positional(1, "task name")?;
+            let path = args.positional(2, "file")?;
+            let language = args.option("language").map(parse_language).transpose()?;
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("cannot read {}: {}", path, e))?;
+            let client = client(true)?;
+            let ws = workspace::TaskWorkspace::open(parent_dir(path))
+                .ok()
+                .filter(|ws| ws.metadata.name == task_name);
+            let sub = match ws {
+                Some(ws) => {
+                    let checked = ws
+                        .submit_checked(
+                            &client,
+                            std::path::Path::new(path),
+                            language,
+                            &run_options(&args),
+                            args.flag("force"),
+                        )
+                        .map_err(error_message)?;
+                    match checked {
+                        runner::CheckedSubmission::Submitted { report, submission } => {
+                            if let Some(report) = &report {
+                                print_report(&ws, report);
+                            }
+                            *submission
+                        }
+                        runner::CheckedSubmission::Refused(report) => {
+                            print_report(&ws, &report);
+                            return Err(String::from(
+                                "the solution fails on the samples, use --force to submit it anyway",
+                            ));
+                        }
+                    }
+                }
+                None => client
+                    .submit_file(task_name, &text, path, language)
+                    .map_err(error_message)?,
+            };
+            if args.flag("json") {
+                print_json(&sub);
+            } else {
+                println!("submitted, id {}", sub.id);
+            }
+        }
+        "test" => {
+            let path = args.positional(1, "file")?;
+            let ws = open_workspace(&args, path)?;
+            if !test_solution(&ws, path, &args)?.passed() {
+                std::process::exit(1);
+            }
+        }
+        "stress" => {
+            let generator = args.positional(1, "generator")?;
+            let brute = args.positional(2, "brute force solution")?;
+            let path = args.positional(3, "file")?;
+            let ws = open_workspace(&args, path)?;
+            let mut options = stress::StressOptions {
+                exact: args.flag("exact"),
+                iterations: args.number("iterations", 1000)?,
+                ..Default::default()
+            };
+            if args.option("seed").is_some() {
+                options.seed = args.number("seed", 0)? as u64;
+            }
+            let outcome = ws
+                .stress(
+                    std::path::Path::new(generator),
+                    std::path::Path::new(brute),
+                    std::path::Path::new(path),
+                    &options,
+                )
+                .map_err(|e| match e {
+                    4 => String::from("unsupported language"),
+                    _ => String::from("cannot run the programs"),
+                })?;
+            match outcome {
+                stress::StressOutcome::Passed(n) => println!("no differences in {} tests", n),
+                stress::StressOutcome::Failed(f) => {
+                    println!("test {} (seed {}): {}", f.iteration, f.seed, f.reason);
+                    println!("input:    {}", f.input.display());
+                    println!("expected: {}", f.expected.display());
+                    println!("got:      {}", f.got.display());
+                    std::process::exit(1);
+                }
+                stress::StressOutcome::CompilationFailed(name, stderr) => {
+                    return Err(format!("compilation of {} failed\n{}", name, stderr));
+                }
+            }
+        }
+        "status" => {
+            let id = args
+                .positional(1, "submission id")?
+                .parse()
+                .map_err(|_| "the submission id must be a number")?;
+            let client = client(true)?;
+            let mut sub = client.get_submission(id).map_err(error_message)?;
+            if args.flag("json") {
+                print_json(&sub);
+            } else {
+                if sub.compilation_outcome.as_deref() == Some("fail") {
+                    // show the diagnostics on the submitted source
+                    if let Ok(explained) = client.explain_compilation(&sub) {
+                        sub.compilation_stderr = Some(explained);
+                    }
+                }
+                print_submission(&sub);
+                if let Some(task) = args.option("task") {
+                    let task = client.get_task(task).map_err(error_message)?;
+                    print_analysis(&sub.analyze(task.time_limit, task.memory_limit, 3));
+                }
+            }
+        }
+        "diff" => {
+            let mut ids = [0; 2];
+            for (i, id) in ids.iter_mut().enumerate() {
+                *id = args
+                    .positional(i + 1, "submission id")?
+                    .parse()
+                    .map_err(|_| "the submission id must be a number")?;
+            }
+            let comparison = client(true)?
+                .compare_submissions(ids[0], ids[1])
+                .map_err(error_message)?;
+            if args.flag("json") {
+                print_json(&comparison);
+                return Ok(());
+            }
+            for file in &comparison.files {
+                print!("{}", file.diff);
+            }
+            for (i, st) in comparison.subtasks.iter().enumerate() {
+                let score = |s: Option<f64>| s.map_or(String::from("-"), |s| s.to_string());
+                println!(
+                    "subtask {}: {} -> {} of {}{}",
+                    st.idx.unwrap_or(i),
+                    score(st.score_a),
+                    score(st.score_b),
+                    st.max_score,
+                    if st.regressed() { "  REGRESSED" } else { "" }
+                );
+                for tc in &st.testcases {
+                    let side = |t: &Option<Testcase>| match t {
+                        Some(t) => format!(
+                            "{:<12} {:.3} s {:>8} KiB",
+                            t.outcome,
+                            t.time,
+                            t.memory / 1024
+                        ),
+                        None => String::from("-"),
+                    };
+                    println!(
+                        "    {:>4} {} -> {}{}",
+                        tc.idx.as_deref().unwrap_or(""),
+                        side(&tc.a),
+                        side(&tc.b),
+                        if tc.outcome_changed() { "  *" } else { "" }
+                    );
+                }
+            }
+        }
+        "submissions" => {
+            let list = client(true)?
+                .get_submission_list(args.positional(1, "task name")?)
+                .map_err(error_message)?;
+            if args.flag("json") {
+                print_json(&list);
+                return Ok(());
+            }
+            for sub in &list.submissions {
+                let outcome = match (&sub.compilation_outcome, sub.score) {
+                    (Some(c), _) if c == "fail" => String::from("compilation failed"),
+                    (_, Some(score)) => format!("{}", score),
+                    _ => String::from("pending"),
+                };
+                println!(
+                    "{:>8}  {}  {}",
+                    sub.id,
+                    date::format_timestamp(sub.timestamp),
+                    outcome
+                );
+            }
+            println!("best score: {}", list.best_score());
+        }
+        "archive" => {
+            let dir = args.positional(1, "directory")?;
+            let options = archive::ArchiveOptions {
+                best_only: args.flag("best"),
+            };
+            let n = client(true)?
+                .archive_submissions(dir, &options)
+                .map_err(error_message)?;
+            println!("archived {} new submissions in {}", n, dir);
+        }
+        "stats" => {
+            let stats = client(false)?
+                .get_stats(args.positional(1, "task name")?)
+                .map_err(error_message)?;
+            if args.flag("json") {
+                print_json(&stats);
+                return Ok(());
+            }
+            println!(
+                "users:       {} correct of {}",
+                stats.nuserscorrect, stats.nusers
+            );
+            println!(
+                "submissions: {} correct of {}",
+                stats.nsubscorrect, stats.nsubs
+            );
+            for (i, s) in stats.best.iter().enumerate() {
+                println!("{:>3}. {:<24} {:.3} s", i + 1, s.username, s.time);
+            }
+        }
+        "help" => println!("{}", USAGE),
+        other => return Err(format!("unknown command {}\n\n{}", other, USAGE)),
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
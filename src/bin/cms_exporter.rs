@@ -0,0 +1,161 @@
+//! `cms_exporter`, serves the training progress of a team as Prometheus metrics
+//!
+//! The configuration file lists the users and the watched tasks, see
+//! `prometheus::ExporterConfig`. Metrics are served at `/metrics` and collected again
+//! every `interval` seconds, failed requests are retried with the default policy.
+
+use cms_tools::metrics::Counters;
+use cms_tools::prometheus::{self, ExporterConfig};
+use cms_tools::retry::RetryPolicy;
+use cms_tools::Client;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// maximum size of the request line and headers
+const MAX_REQUEST: u64 = 8192;
+/// time to read a request, so that slow clients do not keep a thread busy
+const REQUEST_DEADLINE: Duration = Duration::from_secs(10);
+/// maximum number of connections served at the same time, others are closed
+const MAX_CONNECTIONS: usize = 16;
+
+const USAGE: &str = "usage: cms_exporter <config file>
+
+the config file is TOML, or JSON if its extension is json:
+    listen = \"127.0.0.1:9898\"
+    interval = 300
+    users = [\"alice\", \"bob\"]
+    tasks = [\"ois_luck\"]
+    rank_depth = 1000";
+
+/// collect the metrics and render them
+fn collect(client: &Client, counters: &Counters, config: &ExporterConfig) -> String {
+    let mut families = client.collect_progress(config).families();
+    families.extend(prometheus::api_families(&counters.snapshot()));
+    prometheus::render(&families)
+}
+
+/// read the request line and the headers of an HTTP request, returns the request line
+fn read_request(stream: &TcpStream) -> std::io::Result<String> {
+    let deadline = Instant::now() + REQUEST_DEADLINE;
+    let mut reader = BufReader::new(stream.try_clone()?.take(MAX_REQUEST));
+    let mut request = String::new();
+    let mut line = String::new();
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left == Duration::from_secs(0) {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        stream.set_read_timeout(Some(left))?;
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || !line.ends_with('\n') {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "request too long or incomplete",
+            ));
+        }
+        if request.is_empty() {
+            request = line.clone();
+        } else if line.trim().is_empty() {
+            return Ok(request);
+        }
+    }
+}
+
+/// answer an HTTP request with the last collected metrics
+fn respond(mut stream: TcpStream, metrics: &Mutex<String>) -> std::io::Result<()> {
+    let request = read_request(&stream)?;
+    let (status, content_type, body) = match request.split_whitespace().nth(1) {
+        // the first collection has not finished yet
+        Some("/metrics") if metrics.lock().unwrap().is_empty() => (
+            "503 Service Unavailable",
+            "text/plain",
+            String::from("collecting metrics\n"),
+        ),
+        Some("/metrics") => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            metrics.lock().unwrap().clone(),
+        ),
+        Some("/") => (
+            "200 OK",
+            "text/html",
+            String::from("<a href=\"/metrics\">metrics</a>\n"),
+        ),
+        _ => ("404 Not Found", "text/plain", String::from("not found\n")),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+fn run() -> Result<(), String> {
+    let path = match std::env::args().nth(1) {
+        Some(path) if path == "--help" || path == "help" => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Some(path) => path,
+        None => return Err(format!("missing config file\n\n{}", USAGE)),
+    };
+    let config = ExporterConfig::load(&path).map_err(|e| match e {
+        1 => format!("cannot read {}", path),
+        _ => format!("cannot parse {}", path),
+    })?;
+    let listener = TcpListener::bind(&config.listen)
+        .map_err(|e| format!("cannot listen on {}: {}", config.listen, e))?;
+
+    let metrics = Arc::new(Mutex::new(String::new()));
+    let counters = Arc::new(Counters::new());
+    let mut client = Client::new(String::new());
+    client.set_retry_policy(RetryPolicy::default());
+    client.set_metrics(counters.clone());
+    {
+        let metrics = metrics.clone();
+        let config = config.clone();
+        std::thread::spawn(move || loop {
+            let text = collect(&client, &counters, &config);
+            *metrics.lock().unwrap() = text;
+            std::thread::sleep(Duration::from_secs(config.interval.max(1)));
+        });
+    }
+
+    eprintln!("listening on {}", config.listen);
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                continue;
+            }
+        };
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            continue;
+        }
+        let metrics = metrics.clone();
+        let connections = connections.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = respond(stream, &metrics) {
+                eprintln!("error: {}", e);
+            }
+            connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
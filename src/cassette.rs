@@ -0,0 +1,311 @@
+//! Recording and replay of the requests to the api
+//!
+//! A cassette is a JSON file with every request made to the api by a client and its
+//! response. A recording client adds to it every interaction and saves it when the cassette
+//! is ejected, when the client is dropped and on `Client::flush_cassette`. A replaying one
+//! serves the responses from it without using the network, matching requests by endpoint
+//! and payload. Identical requests are answered in the order they were recorded, and with
+//! the last recorded response once they are all used.
+//!
+//! Passwords and tokens are redacted like in the traces before saving, and match
+//! any value when replaying.
+//!
+//! Replaying a request that is not in the cassette returns `Err(1)`, like a request that
+//! could not be sent.
+
+use crate::metrics::redact;
+use crate::Client;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A request with its response
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct Interaction {
+    /// path after `/api/`, like `task` or `files/<digest>/<name>`
+    pub endpoint: String,
+    /// JSON payload, `null` for downloads
+    #[serde(default)]
+    pub request: serde_json::Value,
+    /// HTTP status code of the response
+    pub status: u16,
+    /// body of the response, base64 encoded if `base64` is true
+    pub response: String,
+    /// whether the body is not valid utf-8 and has been base64 encoded
+    #[serde(default)]
+    pub base64: bool,
+}
+impl Interaction {
+    /// make an interaction, encoding the body if needed
+    pub fn new(endpoint: &str, request: serde_json::Value, status: u16, body: &[u8]) -> Self {
+        let (response, base64) = match std::str::from_utf8(body) {
+            Ok(text) => (String::from(text), false),
+            Err(_) => (base64::encode(body), true),
+        };
+        Interaction {
+            endpoint: String::from(endpoint),
+            request,
+            status,
+            response,
+            base64,
+        }
+    }
+    /// get the body of the response
+    pub fn body(&self) -> Vec<u8> {
+        if self.base64 {
+            base64::decode(&self.response).unwrap_or_default()
+        } else {
+            self.response.clone().into_bytes()
+        }
+    }
+}
+
+/// Interactions saved in a file
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+impl Cassette {
+    /// load a cassette from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, u8> {
+        let s = std::fs::read_to_string(path).map_err(|_| 1)?;
+        serde_json::from_str(&s).map_err(|_| 2)
+    }
+    /// save the cassette to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), u8> {
+        let s = serde_json::to_string_pretty(self).map_err(|_| 2)?;
+        std::fs::write(path, s).map_err(|_| 1)
+    }
+}
+
+/// What a client does with its cassette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Record,
+    Replay,
+}
+
+/// Cassette in use by a client
+#[derive(Debug)]
+pub(crate) struct Player {
+    pub(crate) mode: Mode,
+    path: PathBuf,
+    cassette: Cassette,
+    /// whether each interaction has already been replayed
+    used: Vec<bool>,
+    /// whether interactions were recorded since the cassette was last saved
+    unsaved: bool,
+}
+impl Player {
+    /// get the response to a request
+    pub(crate) fn replay(
+        &mut self,
+        endpoint: &str,
+        request: &serde_json::Value,
+    ) -> Option<&Interaction> {
+        let request = redact(request);
+        let matching: Vec<usize> = (0..self.cassette.interactions.len())
+            .filter(|i| {
+                let x = &self.cassette.interactions[*i];
+                x.endpoint == endpoint && redact(&x.request) == request
+            })
+            .collect();
+        let i = matching
+            .iter()
+            .cloned()
+            .find(|i| !self.used[*i])
+            .or_else(|| matching.last().cloned())?;
+        self.used[i] = true;
+        Some(&self.cassette.interactions[i])
+    }
+    /// add an interaction, with its secrets redacted
+    pub(crate) fn record(&mut self, mut interaction: Interaction) {
+        interaction.request = redact(&interaction.request);
+        self.cassette.interactions.push(interaction);
+        self.used.push(true);
+        self.unsaved = true;
+    }
+    /// save the cassette if interactions were recorded since it was last saved
+    pub(crate) fn flush(&mut self) -> Result<(), u8> {
+        if self.unsaved {
+            self.cassette.save(&self.path)?;
+            self.unsaved = false;
+        }
+        Ok(())
+    }
+}
+impl Drop for Player {
+    fn drop(&mut self) {
+        if self.flush().is_err() {
+            tracing::warn!(path = ?self.path, "cannot save the cassette");
+        }
+    }
+}
+
+impl Client {
+    fn use_cassette(&mut self, path: &Path, mode: Mode, cassette: Cassette) {
+        self.cassette = Some(Arc::new(Mutex::new(Player {
+            mode,
+            path: path.to_path_buf(),
+            used: vec![false; cassette.interactions.len()],
+            cassette,
+            unsaved: false,
+        })));
+    }
+
+    /// record every request to the api in the cassette at `path`,
+    /// appending to it if it already exists
+    ///
+    /// the cassette is saved when it is ejected, when the client is dropped and on
+    /// `flush_cassette`; failing to save it is only logged, except on `flush_cassette`
+    pub fn record<P: AsRef<Path>>(&mut self, path: P) -> Result<(), u8> {
+        let path = path.as_ref();
+        let cassette = if path.exists() {
+            Cassette::load(path)?
+        } else {
+            Cassette::default()
+        };
+        self.use_cassette(path, Mode::Record, cassette);
+        Ok(())
+    }
+
+    /// answer every request to the api from the cassette at `path`, without using the network
+    pub fn replay<P: AsRef<Path>>(&mut self, path: P) -> Result<(), u8> {
+        let path = path.as_ref();
+        let cassette = Cassette::load(path)?;
+        self.use_cassette(path, Mode::Replay, cassette);
+        Ok(())
+    }
+
+    /// save the interactions recorded so far, does nothing if not recording
+    pub fn flush_cassette(&self) -> Result<(), u8> {
+        match &self.cassette {
+            Some(player) => player.lock().unwrap().flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// stop recording or replaying, saving the recorded interactions
+    pub fn eject(&mut self) {
+        self.cassette = None;
+    }
+}
+
+/// Cassette file removed when dropped
+#[cfg(test)]
+struct TempFile(PathBuf);
+#[cfg(test)]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// get a client of `username` replaying `interactions`, for the tests of this crate
+///
+/// the cassette is saved to a temporary file which is removed once loaded, even on panic
+#[cfg(test)]
+pub(crate) fn replaying(username: &str, interactions: Vec<Interaction>) -> Client {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let file = TempFile(std::env::temp_dir().join(format!(
+        "cms_tools_cassette_{}_{}.json",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::SeqCst)
+    )));
+    Cassette { interactions }.save(&file.0).unwrap();
+    let mut client = Client::new(String::from(username));
+    client.replay(&file.0).unwrap();
+    client
+}
+
+/// get the response to `get_user` for a user with the given scores, for the tests of this crate
+#[cfg(test)]
+pub(crate) fn user_response(
+    username: &str,
+    score: u32,
+    scores: &[(&str, f64)],
+) -> serde_json::Value {
+    let mut user = crate::compare::tests::user(username, scores);
+    user.score = score;
+    serde_json::to_value(user).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::File;
+
+    #[test]
+    fn replay() {
+        let stats = r#"{"success": 1, "nsubs": 10, "nusers": 4, "nsubscorrect": 3,
+            "nuserscorrect": 2, "best": []}"#;
+        let cassette = Cassette {
+            interactions: vec![
+                Interaction::new(
+                    "task",
+                    serde_json::json!({"action":"stats","name":"ois_luck"}),
+                    200,
+                    stats.as_bytes(),
+                ),
+                Interaction::new(
+                    "task",
+                    serde_json::json!({"action":"stats","name":"ois_sum"}),
+                    200,
+                    stats.replace("\"success\": 1", "\"success\": 0").as_bytes(),
+                ),
+                Interaction::new(
+                    "files/abc/testo.pdf",
+                    serde_json::Value::Null,
+                    200,
+                    &[0xff, 0, 1],
+                ),
+            ],
+        };
+        assert!(cassette.interactions[2].base64);
+
+        let client = replaying("user", cassette.interactions);
+        let s = client.get_stats("ois_luck").unwrap();
+        assert_eq!((s.nsubs, s.nuserscorrect), (10, 2));
+        // replayed again with the last response
+        assert_eq!(client.get_stats("ois_luck").unwrap().nusers, 4);
+        assert_eq!(client.get_stats("ois_sum").err(), Some(3));
+        assert_eq!(client.get_user("someone").err(), Some(1));
+        let file = File {
+            name: String::from("testo.pdf"),
+            digest: String::from("abc"),
+        };
+        assert_eq!(client.get_file_bytes(&file).unwrap(), vec![0xff, 0, 1]);
+    }
+    #[test]
+    fn secrets() {
+        let path =
+            std::env::temp_dir().join(format!("cms_tools_secrets_{}.json", std::process::id()));
+        let mut player = Player {
+            mode: Mode::Record,
+            path: path.clone(),
+            cassette: Cassette::default(),
+            used: Vec::new(),
+            unsaved: false,
+        };
+        let login = |password: &str| serde_json::json!({"action":"login","username":"user","password":password});
+        player.record(Interaction::new(
+            "user",
+            login("secret"),
+            200,
+            br#"{"success": 1}"#,
+        ));
+        assert!(!path.exists());
+        player.flush().unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(!saved.contains("secret"));
+        assert!(player.replay("user", &login("another")).is_some());
+        assert!(player.replay("task", &login("secret")).is_none());
+
+        // saved again when dropped
+        player.record(Interaction::new("task", login("x"), 200, b"{}"));
+        drop(player);
+        let saved = Cassette::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.interactions.len(), 2);
+    }
+}
@@ -0,0 +1,214 @@
+//! Head-to-head comparisons between users
+
+use crate::{Client, Score, Stats, Task, User};
+use std::collections::HashMap;
+
+/// Scores of two users on a task that at least one of them did not fully solve
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TaskScores {
+    pub name: String,
+    pub title: String,
+    pub score_multiplier: f64,
+    /// score of the first user, 0 if never attempted
+    pub score_a: f64,
+    /// score of the second user, 0 if never attempted
+    pub score_b: f64,
+}
+
+/// Best times of two users on a task both of them solved
+///
+/// a time is `None` if the user is not among the fastest ones in the task stats
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TimeComparison {
+    pub name: String,
+    pub time_a: Option<f64>,
+    pub time_b: Option<f64>,
+}
+
+/// Result of `Client::compare_users`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct UserComparison {
+    pub username_a: String,
+    pub username_b: String,
+    /// names of tasks fully solved by both users
+    pub solved_by_both: Vec<String>,
+    /// names of tasks fully solved by the first user only
+    pub solved_only_by_a: Vec<String>,
+    /// names of tasks fully solved by the second user only
+    pub solved_only_by_b: Vec<String>,
+    /// tasks where at least one of the users has a partial score
+    pub partial: Vec<TaskScores>,
+    /// sum over all tasks of `(score_a - score_b) * score_multiplier`
+    pub weighted_difference: f64,
+    /// best time comparisons on tasks solved by both
+    pub times: Vec<TimeComparison>,
+}
+impl UserComparison {
+    /// build a comparison from the scores of two users,
+    /// `tasks` is used to get the score multipliers
+    ///
+    /// `times` is left empty
+    pub fn new(a: &User, b: &User, tasks: &[Task]) -> Self {
+        let multipliers: HashMap<&str, f64> = tasks
+            .iter()
+            .map(|t| (t.name.as_str(), t.score_multiplier))
+            .collect();
+        let empty: Vec<Score> = Vec::new();
+        let scores_a = a.scores.as_ref().unwrap_or(&empty);
+        let scores_b = b.scores.as_ref().unwrap_or(&empty);
+        let map_b: HashMap<&str, &Score> = scores_b.iter().map(|s| (s.name.as_str(), s)).collect();
+        let map_a: HashMap<&str, &Score> = scores_a.iter().map(|s| (s.name.as_str(), s)).collect();
+
+        let mut res = UserComparison {
+            username_a: a.username.clone(),
+            username_b: b.username.clone(),
+            solved_by_both: Vec::new(),
+            solved_only_by_a: Vec::new(),
+            solved_only_by_b: Vec::new(),
+            partial: Vec::new(),
+            weighted_difference: 0.0,
+            times: Vec::new(),
+        };
+        let mut visit = |sc: &Score, score_a: f64, score_b: f64| {
+            let multiplier = *multipliers.get(sc.name.as_str()).unwrap_or(&1.0);
+            res.weighted_difference += (score_a - score_b) * multiplier;
+            let solved_a = score_a >= 100.0;
+            let solved_b = score_b >= 100.0;
+            if solved_a && solved_b {
+                res.solved_by_both.push(sc.name.clone());
+            } else {
+                if solved_a {
+                    res.solved_only_by_a.push(sc.name.clone());
+                } else if solved_b {
+                    res.solved_only_by_b.push(sc.name.clone());
+                }
+                if (score_a > 0.0 && !solved_a) || (score_b > 0.0 && !solved_b) {
+                    res.partial.push(TaskScores {
+                        name: sc.name.clone(),
+                        title: sc.title.clone(),
+                        score_multiplier: multiplier,
+                        score_a,
+                        score_b,
+                    });
+                }
+            }
+        };
+        for sc in scores_a {
+            let score_b = map_b.get(sc.name.as_str()).map_or(0.0, |s| s.score);
+            visit(sc, sc.score, score_b);
+        }
+        for sc in scores_b {
+            if !map_a.contains_key(sc.name.as_str()) {
+                visit(sc, 0.0, sc.score);
+            }
+        }
+        res
+    }
+
+    /// fill the time comparison for task `name` using its stats
+    pub fn add_times(&mut self, name: &str, stats: &Stats) {
+        let find = |username: &str| {
+            stats
+                .best
+                .iter()
+                .find(|s| s.username == username)
+                .map(|s| s.time)
+        };
+        self.times.push(TimeComparison {
+            name: String::from(name),
+            time_a: find(&self.username_a),
+            time_b: find(&self.username_b),
+        });
+    }
+}
+
+impl Client {
+    /// compare the scores and best times of two users
+    ///
+    /// this does a `get_stats` request for every task solved by both users
+    pub fn compare_users(&self, a: &str, b: &str) -> Result<UserComparison, u8> {
+        let user_a = self.get_user(a)?;
+        let user_b = self.get_user(b)?;
+        let tasks = self.get_full_task_list("", None, None)?;
+        let mut res = UserComparison::new(&user_a, &user_b, &tasks.tasks);
+        for name in res.solved_by_both.clone() {
+            let stats = self.get_stats(&name)?;
+            res.add_times(&name, &stats);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{Institute, Stat};
+
+    /// make a user with the given scores, for the tests of this crate
+    pub(crate) fn user(username: &str, scores: &[(&str, f64)]) -> User {
+        User {
+            username: String::from(username),
+            mail_hash: String::new(),
+            first_name: String::new(),
+            last_name: String::new(),
+            institute: Institute {
+                province: None,
+                city: None,
+                region: None,
+                id: None,
+                name: None,
+            },
+            tasks_solved: 0,
+            access_level: 0,
+            join_date: 0.0,
+            score: 0,
+            global_access_level: 0,
+            scores: Some(
+                scores
+                    .iter()
+                    .map(|(name, score)| Score {
+                        score: *score,
+                        name: String::from(*name),
+                        title: String::from(*name),
+                    })
+                    .collect(),
+            ),
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn compare() {
+        let a = user("a", &[("x", 100.0), ("y", 100.0), ("z", 40.0)]);
+        let b = user("b", &[("x", 100.0), ("z", 100.0), ("w", 10.0)]);
+        let tasks = vec![Task {
+            score: None,
+            title: String::from("y"),
+            score_multiplier: 2.0,
+            id: 0,
+            name: String::from("y"),
+        }];
+        let mut cmp = UserComparison::new(&a, &b, &tasks);
+        assert_eq!(cmp.solved_by_both, vec!["x"]);
+        assert_eq!(cmp.solved_only_by_a, vec!["y"]);
+        assert_eq!(cmp.solved_only_by_b, vec!["z"]);
+        assert_eq!(cmp.partial.len(), 2);
+        assert_eq!(cmp.weighted_difference, 200.0 - 60.0 - 10.0);
+        cmp.add_times(
+            "x",
+            &Stats {
+                nsubscorrect: 0,
+                success: 1,
+                nusers: 0,
+                nsubs: 0,
+                nuserscorrect: 0,
+                best: vec![Stat {
+                    username: String::from("b"),
+                    time: 0.5,
+                }],
+            },
+        );
+        assert_eq!(cmp.times[0].time_a, None);
+        assert_eq!(cmp.times[0].time_b, Some(0.5));
+    }
+}
@@ -0,0 +1,518 @@
+//! Backend for contests on CMS ContestWebServer instances
+//!
+//! ContestWebServer has a JSON API only for the status of submissions, everything else
+//! is read from the HTML pages looking just at links and form fields:
+//! * the contest overview links every task as `tasks/<name>/description`
+//! * the description of a task has its title in `<h1>` and links statements as
+//!   `tasks/<name>/statements/<language>` and attachments as `tasks/<name>/attachments/<file>`
+//! * the submissions page of a task has the submit form, with a file input for every file
+//!   of the submission format, and a row with `data-submission="<id>"` for every submission
+//!
+//! Functions here return the same errors as the ones of `Client`.
+
+use crate::language::Language;
+use crate::query::Outcome;
+use crate::Score;
+
+/// submission status codes used by ContestWebServer
+pub mod status {
+    pub const COMPILING: u8 = 1;
+    pub const COMPILATION_FAILED: u8 = 2;
+    pub const EVALUATING: u8 = 3;
+    pub const SCORING: u8 = 4;
+    pub const SCORED: u8 = 5;
+}
+
+/// Task of a contest
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct CwsTask {
+    pub name: String,
+    pub title: String,
+    /// languages of the statements, like `it`
+    pub statements: Vec<String>,
+    /// names of the attachments
+    pub attachments: Vec<String>,
+    /// names of the files to submit, like `sum.%l`
+    pub submission_format: Vec<String>,
+}
+
+fn lenient_score<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<f64>, D::Error> {
+    // scores are numbers or formatted strings depending on the version
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(f64),
+        Text(String),
+    }
+    let value: Option<Value> = serde::Deserialize::deserialize(d)?;
+    Ok(match value {
+        Some(Value::Number(x)) => Some(x),
+        Some(Value::Text(s)) => s.split('/').next().and_then(|s| s.trim().parse().ok()),
+        None => None,
+    })
+}
+
+/// Status of a submission
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct CwsSubmission {
+    #[serde(default)]
+    pub id: usize,
+    /// one of the codes in `status`
+    pub status: u8,
+    pub status_text: String,
+    #[serde(default, deserialize_with = "lenient_score")]
+    pub score: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_score")]
+    pub max_score: Option<f64>,
+    /// score on the public testcases, the only one shown in some contests
+    #[serde(default, deserialize_with = "lenient_score")]
+    pub public_score: Option<f64>,
+}
+impl CwsSubmission {
+    /// get the score, or the public one if the full one is hidden
+    pub fn get_score(&self) -> Option<f64> {
+        if self.status != status::SCORED {
+            return None;
+        }
+        self.score.or(self.public_score)
+    }
+    /// get the outcome, like for submissions on CMSocial
+    pub fn outcome(&self) -> Outcome {
+        if self.status == status::COMPILATION_FAILED {
+            return Outcome::CompilationFailed;
+        }
+        match self.get_score() {
+            None => Outcome::Pending,
+            Some(s) if s >= self.max_score.unwrap_or(100.0) => Outcome::Full,
+            Some(s) if s > 0.0 => Outcome::Partial,
+            _ => Outcome::Zero,
+        }
+    }
+}
+
+/// get the values of an attribute in every tag named `tag`
+fn tag_attributes<'a>(html: &'a str, tag: &str, attribute: &str) -> Vec<(&'a str, &'a str)> {
+    let open = format!("<{}", tag);
+    let key = format!("{}=\"", attribute);
+    let mut res = Vec::new();
+    let mut rest = html;
+    while let Some(pos) = rest.find(&open) {
+        rest = &rest[pos + open.len()..];
+        let end = rest.find('>').unwrap_or(rest.len());
+        let inside = &rest[..end];
+        if let Some(start) = inside.find(&key) {
+            let value = &inside[start + key.len()..];
+            if let Some(len) = value.find('"') {
+                res.push((inside, &value[..len]));
+            }
+        }
+    }
+    res
+}
+
+/// get the value of the input named `name`
+fn input_value(html: &str, name: &str) -> Option<String> {
+    let name = format!("name=\"{}\"", name);
+    tag_attributes(html, "input", "value")
+        .into_iter()
+        .find(|(tag, _)| tag.contains(&name))
+        .map(|(_, value)| unescape(value))
+}
+
+/// get the last segment of the links like `tasks/<task>/<kind>/<last>`, without duplicates
+fn task_links(html: &str, task: Option<&str>, kind: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    for (_, href) in tag_attributes(html, "a", "href") {
+        let segments: Vec<&str> = match href.find("tasks/") {
+            Some(pos) => href[pos..].split('/').collect(),
+            None => continue,
+        };
+        let value = match (task, segments.as_slice()) {
+            (None, [_, name, k]) if *k == kind => name,
+            (Some(t), [_, name, k, last]) if *name == t && *k == kind => last,
+            _ => continue,
+        };
+        let value = unescape(value);
+        if !res.contains(&value) {
+            res.push(value);
+        }
+    }
+    res
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// **Client** for a contest on a ContestWebServer instance
+pub struct CwsClient {
+    pub client: reqwest::Client,
+    /// url of the contest, like `http://localhost:8888/` or `https://example.com/ois/`
+    pub base_url: String,
+    pub username: String,
+    /// if client has the login cookie, this should be true and false otherwise
+    pub logged: bool,
+}
+
+impl CwsClient {
+    /// make a new client for the contest at `base_url`
+    pub fn new(base_url: &str, username: String) -> Self {
+        let mut base_url = String::from(base_url);
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        CwsClient {
+            client: reqwest::Client::builder()
+                .cookie_store(true)
+                .build()
+                .unwrap(),
+            base_url,
+            username,
+            logged: false,
+        }
+    }
+
+    fn get_response(&self, path: &str) -> Result<reqwest::Response, u8> {
+        let response = self
+            .client
+            .get(&format!("{}{}", self.base_url, path))
+            .send()
+            .map_err(|_| 1)?;
+        if !response.status().is_success() {
+            return Err(3);
+        }
+        Ok(response)
+    }
+
+    fn get_page(&self, path: &str) -> Result<String, u8> {
+        self.get_response(path)?.text().map_err(|_| 2)
+    }
+
+    pub(crate) fn get_bytes(&self, path: &str) -> Result<Vec<u8>, u8> {
+        let mut res = Vec::new();
+        self.get_response(path)?.copy_to(&mut res).map_err(|_| 1)?;
+        Ok(res)
+    }
+
+    /// login with self.username and password
+    ///
+    /// Returns `Ok(true)` if the client was already logged and `Ok(false)` if it was not and succeeds in logging
+    pub fn login(&mut self, password: &str) -> Result<bool, u8> {
+        if self.logged {
+            return Ok(true);
+        }
+        let page = self.get_page("")?;
+        let xsrf = input_value(&page, "_xsrf").unwrap_or_default();
+        let response = self
+            .client
+            .post(&format!("{}login", self.base_url))
+            .form(&[
+                ("username", self.username.as_str()),
+                ("password", password),
+                ("next", "/"),
+                ("_xsrf", xsrf.as_str()),
+            ])
+            .send()
+            .map_err(|_| 1)?;
+        if !response.status().is_success() || response.url().as_str().contains("login_error") {
+            return Err(3);
+        }
+        self.logged = true;
+        Ok(false)
+    }
+
+    /// get the names of the tasks of the contest
+    pub fn get_task_list(&self) -> Result<Vec<String>, u8> {
+        Ok(task_links(&self.get_page("")?, None, "description"))
+    }
+
+    /// get a task, with its submission format
+    pub fn get_task(&self, name: &str) -> Result<CwsTask, u8> {
+        let page = self.get_page(&format!("tasks/{}/description", name))?;
+        let title = match (page.find("<h1"), page.find("</h1>")) {
+            (Some(start), Some(end)) if start < end => {
+                let inside = &page[start..end];
+                unescape(inside[inside.find('>').map_or(0, |p| p + 1)..].trim())
+            }
+            _ => String::from(name),
+        };
+        let submissions = self.get_page(&format!("tasks/{}/submissions", name))?;
+        Ok(CwsTask {
+            name: String::from(name),
+            title,
+            statements: task_links(&page, Some(name), "statements"),
+            attachments: task_links(&page, Some(name), "attachments"),
+            submission_format: tag_attributes(&submissions, "input", "name")
+                .into_iter()
+                .filter(|(tag, _)| tag.contains("type=\"file\""))
+                .map(|(_, name)| unescape(name))
+                .collect(),
+        })
+    }
+
+    /// download the statement of a task in `language`, usually a pdf
+    pub fn get_statement(&self, task_name: &str, language: &str) -> Result<Vec<u8>, u8> {
+        self.get_bytes(&format!("tasks/{}/statements/{}", task_name, language))
+    }
+
+    /// download an attachment of a task
+    pub fn get_attachment(&self, task_name: &str, filename: &str) -> Result<Vec<u8>, u8> {
+        self.get_bytes(&format!("tasks/{}/attachments/{}", task_name, filename))
+    }
+
+    /// get the ids of the submissions of the logged user on a task, newest first
+    pub fn get_submission_ids(&self, task_name: &str) -> Result<Vec<usize>, u8> {
+        let page = self.get_page(&format!("tasks/{}/submissions", task_name))?;
+        Ok(tag_attributes(&page, "tr", "data-submission")
+            .into_iter()
+            .filter_map(|(_, id)| id.parse().ok())
+            .collect())
+    }
+
+    /// get the status of a submission
+    pub fn get_submission(&self, task_name: &str, id: usize) -> Result<CwsSubmission, u8> {
+        let mut res: CwsSubmission = self
+            .get_response(&format!("tasks/{}/submissions/{}", task_name, id))?
+            .json()
+            .map_err(|_| 2)?;
+        res.id = id;
+        Ok(res)
+    }
+
+    /// submit a not output-only task, returns the id of the new submission
+    ///
+    /// the language of `filename` is recognized like in `Client::submit_file`
+    pub fn submit(&self, task_name: &str, text: &str, filename: &str) -> Result<usize, u8> {
+        let language = Language::detect(filename, text).ok_or(4)?;
+        let path = format!("tasks/{}/submissions", task_name);
+        let page = self.get_page(&path)?;
+        let before: Vec<usize> = self.get_submission_ids(task_name)?;
+        let field = tag_attributes(&page, "input", "name")
+            .into_iter()
+            .find(|(tag, _)| tag.contains("type=\"file\""))
+            .map(|(_, name)| unescape(name))
+            .ok_or(2)?;
+        let filename = std::path::Path::new(filename)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(filename);
+        let form = reqwest::multipart::Form::new()
+            .text("_xsrf", input_value(&page, "_xsrf").unwrap_or_default())
+            .text("language", language.server_name())
+            .part(
+                field,
+                reqwest::multipart::Part::bytes(text.as_bytes().to_vec())
+                    .file_name(String::from(filename)),
+            );
+        let response = self
+            .client
+            .post(&format!("{}tasks/{}/submit", self.base_url, task_name))
+            .multipart(form)
+            .send()
+            .map_err(|_| 1)?;
+        if !response.status().is_success() {
+            return Err(3);
+        }
+        // the server redirects to the submissions page, with a notification if it refused
+        self.get_submission_ids(task_name)?
+            .into_iter()
+            .find(|id| !before.contains(id))
+            .ok_or(3)
+    }
+
+    /// get the best score of the logged user on every task
+    pub fn get_scores(&self) -> Result<Vec<Score>, u8> {
+        let mut res = Vec::new();
+        for name in self.get_task_list()? {
+            let mut score: f64 = 0.0;
+            for id in self.get_submission_ids(&name)? {
+                if let Some(s) = self.get_submission(&name, id)?.get_score() {
+                    score = score.max(s);
+                }
+            }
+            res.push(Score {
+                score,
+                title: self.get_task(&name)?.title,
+                name,
+            });
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// read a request, returning the request line, the headers and the body
+    fn read_request(reader: &mut BufReader<std::net::TcpStream>) -> (String, String, String) {
+        let mut first = String::new();
+        reader.read_line(&mut first).unwrap();
+        let mut headers = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            headers += &line.to_lowercase();
+        }
+        let length = headers
+            .lines()
+            .find_map(|l| l.strip_prefix("content-length:"))
+            .map_or(0, |l| l.trim().parse().unwrap());
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        (first, headers, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    const LOGIN_PAGE: &str = r#"<form action="/ois/login" method="post">
+<input type="hidden" name="_xsrf" value="tok">
+<input type="text" name="username"><input type="password" name="password">
+</form>"#;
+    const OVERVIEW_PAGE: &str = r#"<ul class="nav">
+<li><a href="/ois/tasks/sum/description">Statement</a></li>
+<li><a href="/ois/tasks/sum/submissions">Submissions</a></li>
+</ul>"#;
+    const DESCRIPTION_PAGE: &str = r#"<h1 class="title">A &amp; B (sum)</h1>
+<a href="/ois/tasks/sum/statements/it">Italian</a>
+<a href="/ois/tasks/sum/attachments/sum.zip">sum.zip</a>"#;
+    const SUBMIT_FORM: &str = r#"<form action="/ois/tasks/sum/submit" method="post">
+<input type="hidden" name="_xsrf" value="tok">
+<input type="file" name="sum.%l">
+</form>"#;
+
+    /// response of the stand-in contest, as status, headers and body
+    fn respond(
+        post: bool,
+        path: &str,
+        logged: bool,
+        body: &str,
+        ids: &mut Vec<usize>,
+    ) -> (&'static str, &'static str, String) {
+        let redirect = "302 Found";
+        match (post, path) {
+            (true, "/ois/login") if body.contains("password=secret") => (
+                redirect,
+                "Set-Cookie: ois_login=ok; Path=/\r\nLocation: /ois/\r\n",
+                String::new(),
+            ),
+            (true, "/ois/login") => (
+                redirect,
+                "Location: /ois/?login_error=true\r\n",
+                String::new(),
+            ),
+            (false, p) if !logged && (p == "/ois/" || p.starts_with("/ois/?")) => {
+                ("200 OK", "", String::from(LOGIN_PAGE))
+            }
+            (false, "/ois/") => ("200 OK", "", String::from(OVERVIEW_PAGE)),
+            (false, "/ois/tasks/sum/description") => ("200 OK", "", String::from(DESCRIPTION_PAGE)),
+            (false, "/ois/tasks/sum/statements/it") => ("200 OK", "", String::from("%PDF")),
+            (false, "/ois/tasks/sum/submissions") => {
+                let rows: String = ids
+                    .iter()
+                    .rev()
+                    .map(|id| format!("<tr data-submission=\"{}\"><td>{}</td></tr>\n", id, id))
+                    .collect();
+                (
+                    "200 OK",
+                    "",
+                    format!("{}<table>\n{}</table>", SUBMIT_FORM, rows),
+                )
+            }
+            (true, "/ois/tasks/sum/submit")
+                if logged && body.contains("tok") && body.contains("C++17 / g++") =>
+            {
+                ids.push(ids.len() + 1);
+                (
+                    redirect,
+                    "Location: /ois/tasks/sum/submissions\r\n",
+                    String::new(),
+                )
+            }
+            (false, "/ois/tasks/sum/submissions/1") => (
+                "200 OK",
+                "",
+                String::from(
+                    r#"{"status": 5, "status_text": "Evaluated", "score": "40 / 100", "max_score": 100}"#,
+                ),
+            ),
+            (false, "/ois/tasks/sum/submissions/2") => (
+                "200 OK",
+                "",
+                String::from(r#"{"status": 3, "status_text": "Evaluating..."}"#),
+            ),
+            _ => ("404 Not Found", "", String::new()),
+        }
+    }
+
+    /// serve a stand-in contest with the task `sum`, returns its url
+    fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/ois/", listener.local_addr().unwrap());
+        let submissions = Arc::new(Mutex::new(vec![1usize]));
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let (first, headers, body) = read_request(&mut reader);
+                let (status, extra, content) = respond(
+                    first.starts_with("POST"),
+                    first.split_whitespace().nth(1).unwrap(),
+                    headers.contains("ois_login=ok"),
+                    &body,
+                    &mut submissions.lock().unwrap(),
+                );
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    extra,
+                    content.len(),
+                    content
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn contest() {
+        let url = serve();
+        let mut client = CwsClient::new(&url, String::from("user"));
+        assert_eq!(client.login("wrong"), Err(3));
+        assert_eq!(client.login("secret"), Ok(false));
+        assert_eq!(client.get_task_list().unwrap(), vec![String::from("sum")]);
+        let task = client.get_task("sum").unwrap();
+        assert_eq!(task.title, "A & B (sum)");
+        assert_eq!(task.statements, vec![String::from("it")]);
+        assert_eq!(task.attachments, vec![String::from("sum.zip")]);
+        assert_eq!(task.submission_format, vec![String::from("sum.%l")]);
+        assert_eq!(client.get_statement("sum", "it").unwrap(), b"%PDF");
+        assert_eq!(client.get_statement("sum", "en"), Err(3));
+        let sub = client.get_submission("sum", 1).unwrap();
+        assert_eq!(sub.get_score(), Some(40.0));
+        assert_eq!(sub.outcome(), Outcome::Partial);
+        let id = client.submit("sum", "int main(){}", "src/sum.cpp").unwrap();
+        assert_eq!(id, 2);
+        assert_eq!(client.get_submission_ids("sum").unwrap(), vec![2, 1]);
+        assert_eq!(
+            client.get_submission("sum", 2).unwrap().outcome(),
+            Outcome::Pending
+        );
+        let judge: &dyn crate::judge::Judge = &client;
+        let statement = &judge.task("sum").unwrap().statements[0];
+        assert_eq!(judge.download(statement).unwrap(), b"%PDF");
+        assert_eq!(judge.best_score("sum"), Ok(40.0));
+        let scores = client.get_scores().unwrap();
+        assert_eq!((scores[0].score, scores[0].name.as_str()), (40.0, "sum"));
+    }
+}
@@ -0,0 +1,59 @@
+//! Conversion between unix timestamps and dates in UTC
+//!
+//! The algorithms are from <http://howardhinnant.github.io/date_algorithms.html>.
+
+/// get the number of days since 1970-01-01 of a date, without checking that it is valid
+pub fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// get the year, month and day of the date `days` days after 1970-01-01
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + if m <= 2 { 1 } else { 0 }, m, d)
+}
+
+/// format a unix timestamp as `YYYY-MM-DD HH:MM:SS` in UTC
+pub fn format_timestamp(timestamp: f64) -> String {
+    let secs = timestamp as i64;
+    let (y, m, d) = civil_from_days(secs.div_euclid(86400));
+    let rem = secs.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        y,
+        m,
+        d,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2020, 3, 1), 18322);
+        for days in &[-800_000, -1, 0, 59, 18321, 18322, 1_000_000] {
+            let (y, m, d) = civil_from_days(*days);
+            assert_eq!(days_from_civil(y, m, d), *days);
+        }
+        assert_eq!(format_timestamp(1583020799.5), "2020-02-29 23:59:59");
+        assert_eq!(format_timestamp(-1.0), "1969-12-31 23:59:59");
+    }
+}
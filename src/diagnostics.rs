@@ -0,0 +1,247 @@
+//! Structured compiler diagnostics from the compilation output of submissions
+//!
+//! Recognized formats are the ones of GCC and Clang (`file:line:column: error: message`),
+//! javac (`File.java:line: error: message`, with the column taken from the caret line)
+//! and FPC (`file(line,column) Error: message`).
+
+use crate::{Client, DetailedSubmission};
+use std::fmt;
+
+/// Severity of a diagnostic
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// notes and hints
+    Note,
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        })
+    }
+}
+
+/// A message of the compiler about a line of a file
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    /// 1-based
+    pub line: usize,
+    /// 1-based, `None` if the compiler does not give it
+    pub column: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+fn severity(word: &str) -> Option<Severity> {
+    match word {
+        "error" | "fatal error" | "Error" | "Fatal" => Some(Severity::Error),
+        "warning" | "Warning" => Some(Severity::Warning),
+        "note" | "Note" | "Hint" => Some(Severity::Note),
+        _ => None,
+    }
+}
+
+/// parse `file:line[:column]: severity: message`
+fn parse_gcc(line: &str) -> Option<Diagnostic> {
+    for word in &["fatal error", "error", "warning", "note"] {
+        let sep = format!(": {}: ", word);
+        if let Some(pos) = line.find(&sep) {
+            let mut location = line[..pos].rsplitn(3, ':');
+            let last = location.next()?.parse().ok()?;
+            let (file, line_number, column) = match location.next()?.parse() {
+                Ok(l) => (location.next()?, l, Some(last)),
+                Err(_) => (line[..pos].rsplit_once(':')?.0, last, None),
+            };
+            return Some(Diagnostic {
+                file: String::from(file),
+                line: line_number,
+                column,
+                severity: severity(word)?,
+                message: String::from(&line[pos + sep.len()..]),
+            });
+        }
+    }
+    None
+}
+
+/// parse `file(line,column) Severity: message`
+fn parse_fpc(line: &str) -> Option<Diagnostic> {
+    let (file, rest) = line.split_once('(')?;
+    let (location, rest) = rest.split_once(") ")?;
+    let (word, message) = rest.split_once(": ")?;
+    let (line_number, column) = match location.split_once(',') {
+        Some((l, c)) => (l.parse().ok()?, Some(c.parse().ok()?)),
+        None => (location.parse().ok()?, None),
+    };
+    Some(Diagnostic {
+        file: String::from(file),
+        line: line_number,
+        column,
+        severity: severity(word)?,
+        message: String::from(message),
+    })
+}
+
+/// parse the diagnostics in the output of a compiler, ignoring the other lines
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let mut res: Vec<Diagnostic> = Vec::new();
+    // lines since the last diagnostic, javac prints the source line and then the caret
+    let mut since_last = 0;
+    for line in output.lines() {
+        since_last += 1;
+        if let Some(d) = parse_gcc(line).or_else(|| parse_fpc(line)) {
+            res.push(d);
+            since_last = 0;
+            continue;
+        }
+        let trimmed = line.trim();
+        if since_last <= 2 && trimmed == "^" {
+            if let Some(last) = res.last_mut() {
+                if last.column.is_none() {
+                    last.column = Some(line.find('^').unwrap() + 1);
+                }
+            }
+        }
+    }
+    res
+}
+
+/// render diagnostics showing the lines of `sources` they refer to, like
+/// ```text
+/// error: 'x' was not declared in this scope
+///   --> sol.cpp:5:3
+///   |
+/// 5 |   x = 1;
+///   |   ^
+/// ```
+/// `sources` are pairs of file name and content, a diagnostic refers to the file with
+/// the same name without directories, or to the only file if there is just one
+pub fn render(diagnostics: &[Diagnostic], sources: &[(String, String)]) -> String {
+    let mut res = String::new();
+    for d in diagnostics {
+        res += &format!("{}: {}\n", d.severity, d.message);
+        let base = d.file.rsplit(['/', '\\']).next().unwrap_or(&d.file);
+        let source = sources
+            .iter()
+            .find(|(name, _)| name == base)
+            .or(if sources.len() == 1 {
+                sources.first()
+            } else {
+                None
+            });
+        let location = match d.column {
+            Some(c) => format!("{}:{}:{}", base, d.line, c),
+            None => format!("{}:{}", base, d.line),
+        };
+        let code = source.and_then(|(_, s)| s.lines().nth(d.line.wrapping_sub(1)));
+        let code = match code {
+            Some(code) => code,
+            None => {
+                res += &format!(" --> {}\n\n", location);
+                continue;
+            }
+        };
+        let pad = " ".repeat(d.line.to_string().len());
+        res += &format!(
+            "{} --> {}\n{} |\n{} | {}\n",
+            pad, location, pad, d.line, code
+        );
+        if let Some(c) = d.column {
+            // keep tabs so that the caret is aligned with the code
+            let indent: String = code
+                .chars()
+                .take(c.saturating_sub(1))
+                .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+                .collect();
+            res += &format!("{} | {}^\n", pad, indent);
+        }
+        res += "\n";
+    }
+    res
+}
+
+impl DetailedSubmission {
+    /// get the diagnostics in the compilation output
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.compilation_stderr
+            .as_deref()
+            .map(parse_diagnostics)
+            .unwrap_or_default()
+    }
+}
+
+impl Client {
+    /// render the diagnostics of a submission on its files
+    ///
+    /// if no diagnostic is recognized, the raw compilation output is returned
+    pub fn explain_compilation(&self, sub: &DetailedSubmission) -> Result<String, u8> {
+        let diagnostics = sub.diagnostics();
+        if diagnostics.is_empty() {
+            return Ok(sub.compilation_stderr.clone().unwrap_or_default());
+        }
+        let mut sources = Vec::new();
+        for file in &sub.files {
+            sources.push((file.name.clone(), self.get_file(file)?));
+        }
+        Ok(render(&diagnostics, &sources))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let gcc = "sol.cpp: In function 'int main()':\n\
+                   sol.cpp:5:3: error: 'x' was not declared in this scope\n    \
+                   5 |   x = 1;\n      |   ^\n\
+                   /tmp/sol.c:2: warning: no newline at end of file\n";
+        let d = parse_diagnostics(gcc);
+        assert_eq!(d.len(), 2);
+        assert_eq!(d[0].file, "sol.cpp");
+        assert_eq!((d[0].line, d[0].column), (5, Some(3)));
+        assert_eq!(d[0].severity, Severity::Error);
+        assert_eq!(d[0].message, "'x' was not declared in this scope");
+        assert_eq!((d[1].file.as_str(), d[1].column), ("/tmp/sol.c", None));
+        assert_eq!(d[1].severity, Severity::Warning);
+
+        let fpc = "Compiling sol.pas\nsol.pas(7,10) Error: Identifier not found \"y\"\n\
+                   sol.pas(9,1) Fatal: There were 1 errors compiling module, stopping\n";
+        let d = parse_diagnostics(fpc);
+        assert_eq!(d.len(), 2);
+        assert_eq!((d[0].line, d[0].column), (7, Some(10)));
+        assert_eq!(d[0].message, "Identifier not found \"y\"");
+        assert_eq!(d[1].severity, Severity::Error);
+
+        let javac = "Main.java:3: error: cannot find symbol\n        x = 1;\n        ^\n  \
+                     symbol:   variable x\n1 error\n";
+        let d = parse_diagnostics(javac);
+        assert_eq!(d.len(), 1);
+        assert_eq!((d[0].line, d[0].column), (3, Some(9)));
+    }
+
+    #[test]
+    fn render_on_source() {
+        let d = parse_diagnostics("/box/sol.cpp:2:5: error: expected ';' before '}' token\n");
+        let sources = vec![(
+            String::from("sol.cpp"),
+            String::from("int main() {\n\treturn 0\n}\n"),
+        )];
+        assert_eq!(
+            render(&d, &sources),
+            "error: expected ';' before '}' token\n  --> sol.cpp:2:5\n  |\n2 | \treturn 0\n  | \t   ^\n\n"
+        );
+        // some tools count columns from 0
+        let d = parse_diagnostics("sol.cpp:1:0: warning: no newline at end of file\n");
+        assert_eq!(
+            render(&d, &sources),
+            "warning: no newline at end of file\n  --> sol.cpp:1:0\n  |\n1 | int main() {\n  | ^\n\n"
+        );
+    }
+}
@@ -0,0 +1,367 @@
+//! Comparison of two submissions: source diff and per-testcase results
+//!
+//! Subtasks and testcases are matched by position, since submissions for the same task
+//! are evaluated on the same testcases.
+
+use crate::{Client, DetailedSubmission, ScoreDetail, Testcase};
+
+/// lines of context around changes in unified diffs
+const CONTEXT: usize = 3;
+
+/// A line of a line-by-line diff
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    /// only in the first text
+    Removed(String),
+    /// only in the second text
+    Added(String),
+}
+
+/// get a shortest line-by-line diff between `a` and `b`, using Myers' algorithm
+pub fn diff_lines(a: &str, b: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = a.lines().collect();
+    let b: Vec<&str> = b.lines().collect();
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let offset = n + m + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // diagonals -d-1..=d+1 of v before every step d, used to walk back the path,
+    // the only ones read by step d
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    // `v[(k + base) as usize]` is the furthest x on diagonal k
+    let prev_k = |v: &[isize], base: isize, d: isize, k: isize| {
+        if k == -d || (k != d && v[(k - 1 + base) as usize] < v[(k + 1 + base) as usize]) {
+            k + 1
+        } else {
+            k - 1
+        }
+    };
+    'search: for d in 0..=(n + m) {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let pk = prev_k(&v, offset, d, k);
+            let mut x = v[(pk + offset) as usize] + if pk == k - 1 { 1 } else { 0 };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(k + offset) as usize] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+    let mut res = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let pk = prev_k(v, d + 1, d, x - y);
+        let px = v[(pk + d + 1) as usize];
+        let py = px - pk;
+        while x > px && y > py {
+            x -= 1;
+            y -= 1;
+            res.push(DiffLine::Same(String::from(a[x as usize])));
+        }
+        if d > 0 {
+            if x == px {
+                y -= 1;
+                res.push(DiffLine::Added(String::from(b[y as usize])));
+            } else {
+                x -= 1;
+                res.push(DiffLine::Removed(String::from(a[x as usize])));
+            }
+        }
+    }
+    res.reverse();
+    res
+}
+
+/// get a unified diff between `a` and `b`, empty if they have the same lines
+pub fn unified_diff(name_a: &str, name_b: &str, a: &str, b: &str) -> String {
+    let lines = diff_lines(a, b);
+    let changes: Vec<usize> = (0..lines.len())
+        .filter(|i| !matches!(lines[*i], DiffLine::Same(_)))
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+    // ranges of lines to show, merging the ones whose context overlaps
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for i in changes {
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    let mut res = format!("--- a/{}\n+++ b/{}\n", name_a, name_b);
+    // lines of each text before the current position
+    let (mut pos, mut line_a, mut line_b) = (0, 0, 0);
+    for (start, end) in hunks {
+        for line in &lines[pos..start] {
+            match line {
+                DiffLine::Same(_) => {
+                    line_a += 1;
+                    line_b += 1;
+                }
+                DiffLine::Removed(_) => line_a += 1,
+                DiffLine::Added(_) => line_b += 1,
+            }
+        }
+        let mut body = String::new();
+        let (mut len_a, mut len_b) = (0, 0);
+        for line in &lines[start..end] {
+            match line {
+                DiffLine::Same(s) => {
+                    len_a += 1;
+                    len_b += 1;
+                    body += &format!(" {}\n", s);
+                }
+                DiffLine::Removed(s) => {
+                    len_a += 1;
+                    body += &format!("-{}\n", s);
+                }
+                DiffLine::Added(s) => {
+                    len_b += 1;
+                    body += &format!("+{}\n", s);
+                }
+            }
+        }
+        // an empty range starts at the line before it
+        let start_a = if len_a == 0 { line_a } else { line_a + 1 };
+        let start_b = if len_b == 0 { line_b } else { line_b + 1 };
+        res += &format!(
+            "@@ -{},{} +{},{} @@\n{}",
+            start_a, len_a, start_b, len_b, body
+        );
+        line_a += len_a;
+        line_b += len_b;
+        pos = end;
+    }
+    res
+}
+
+/// Results of the two submissions on a testcase
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TestcaseComparison {
+    pub idx: Option<String>,
+    /// `None` if the first submission has no such testcase
+    pub a: Option<Testcase>,
+    /// `None` if the second submission has no such testcase
+    pub b: Option<Testcase>,
+}
+impl TestcaseComparison {
+    /// whether the outcome is different
+    pub fn outcome_changed(&self) -> bool {
+        self.a.as_ref().map(|t| &t.outcome) != self.b.as_ref().map(|t| &t.outcome)
+    }
+    /// get the time of the second submission minus the one of the first
+    pub fn time_difference(&self) -> Option<f64> {
+        Some(self.b.as_ref()?.time - self.a.as_ref()?.time)
+    }
+    /// get the memory of the second submission minus the one of the first
+    pub fn memory_difference(&self) -> Option<i64> {
+        Some(self.b.as_ref()?.memory as i64 - self.a.as_ref()?.memory as i64)
+    }
+}
+
+/// Results of the two submissions on a subtask
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SubtaskComparison {
+    pub idx: Option<usize>,
+    pub max_score: f64,
+    /// `None` if the first submission has no such subtask
+    pub score_a: Option<f64>,
+    /// `None` if the second submission has no such subtask
+    pub score_b: Option<f64>,
+    pub testcases: Vec<TestcaseComparison>,
+}
+impl SubtaskComparison {
+    /// whether the second submission has a lower score than the first one
+    pub fn regressed(&self) -> bool {
+        matches!((self.score_a, self.score_b), (Some(a), Some(b)) if b < a)
+    }
+}
+
+/// compare the score details of two submissions
+pub fn compare_score_details(a: &[ScoreDetail], b: &[ScoreDetail]) -> Vec<SubtaskComparison> {
+    (0..a.len().max(b.len()))
+        .map(|i| {
+            let (sa, sb) = (a.get(i), b.get(i));
+            let empty = Vec::new();
+            let ta = sa.map_or(&empty, |s| &s.testcases);
+            let tb = sb.map_or(&empty, |s| &s.testcases);
+            let testcases = (0..ta.len().max(tb.len()))
+                .map(|j| {
+                    let (ca, cb) = (ta.get(j).cloned(), tb.get(j).cloned());
+                    TestcaseComparison {
+                        idx: ca.as_ref().or(cb.as_ref()).and_then(|t| t.idx.clone()),
+                        a: ca,
+                        b: cb,
+                    }
+                })
+                .collect();
+            SubtaskComparison {
+                idx: sa.or(sb).and_then(|s| s.idx),
+                max_score: sa.or(sb).map_or(0.0, |s| s.max_score),
+                score_a: sa.map(|s| s.score),
+                score_b: sb.map(|s| s.score),
+                testcases,
+            }
+        })
+        .collect()
+}
+
+/// Unified diff of a file of the two submissions
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct FileDiff {
+    /// `None` if the first submission has no such file
+    pub name_a: Option<String>,
+    /// `None` if the second submission has no such file
+    pub name_b: Option<String>,
+    /// empty if the files are the same
+    pub diff: String,
+}
+
+/// Result of `Client::compare_submissions`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SubmissionComparison {
+    pub a: DetailedSubmission,
+    pub b: DetailedSubmission,
+    pub files: Vec<FileDiff>,
+    /// empty if a submission has not been evaluated
+    pub subtasks: Vec<SubtaskComparison>,
+}
+impl SubmissionComparison {
+    /// get the subtasks where the second submission has a lower score than the first one
+    pub fn regressions(&self) -> Vec<&SubtaskComparison> {
+        self.subtasks.iter().filter(|s| s.regressed()).collect()
+    }
+}
+
+impl Client {
+    /// compare two submissions, usually an older one `a` and a newer one `b`
+    pub fn compare_submissions(&self, a: usize, b: usize) -> Result<SubmissionComparison, u8> {
+        let a = self.get_submission(a)?;
+        let b = self.get_submission(b)?;
+        let mut files = Vec::new();
+        for i in 0..a.files.len().max(b.files.len()) {
+            let (fa, fb) = (a.files.get(i), b.files.get(i));
+            let source_a = match fa {
+                Some(f) => self.get_file(f)?,
+                None => String::new(),
+            };
+            let source_b = match fb {
+                Some(f) => self.get_file(f)?,
+                None => String::new(),
+            };
+            let name_a = fa.map(|f| f.name.clone());
+            let name_b = fb.map(|f| f.name.clone());
+            let diff = unified_diff(
+                name_a.as_deref().or(name_b.as_deref()).unwrap_or_default(),
+                name_b.as_deref().or(name_a.as_deref()).unwrap_or_default(),
+                &source_a,
+                &source_b,
+            );
+            files.push(FileDiff {
+                name_a,
+                name_b,
+                diff,
+            });
+        }
+        let subtasks = match (&a.score_details, &b.score_details) {
+            (Some(da), Some(db)) => compare_score_details(da, db),
+            _ => Vec::new(),
+        };
+        Ok(SubmissionComparison {
+            a,
+            b,
+            files,
+            subtasks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified() {
+        let a = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let b = "a\nb\nc\nd\nE\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(
+            unified_diff("x.cpp", "x.cpp", a, b),
+            "--- a/x.cpp\n+++ b/x.cpp\n\
+             @@ -2,9 +2,10 @@\n b\n c\n d\n-e\n+E\n f\n g\n h\n i\n j\n+k\n"
+        );
+        assert_eq!(unified_diff("x", "x", a, a), "");
+        assert_eq!(
+            unified_diff("x", "x", "", "a\n"),
+            "--- a/x\n+++ b/x\n@@ -0,0 +1,1 @@\n+a\n"
+        );
+        let lines = diff_lines("a\nb\nc", "b\nc\nd");
+        assert_eq!(
+            lines,
+            vec![
+                DiffLine::Removed(String::from("a")),
+                DiffLine::Same(String::from("b")),
+                DiffLine::Same(String::from("c")),
+                DiffLine::Added(String::from("d")),
+            ]
+        );
+
+        // long files with a few changes
+        let a: String = (0..20000).map(|i| format!("{}\n", i)).collect();
+        let b: String = (0..20000)
+            .map(|i| match i % 5000 {
+                7 => String::new(),
+                8 => format!("{}\nnew\n", i),
+                _ => format!("{}\n", i),
+            })
+            .collect();
+        let lines = diff_lines(&a, &b);
+        let changed: Vec<&DiffLine> = lines
+            .iter()
+            .filter(|l| !matches!(l, DiffLine::Same(_)))
+            .collect();
+        assert_eq!(changed.len(), 8);
+        assert_eq!(changed[0], &DiffLine::Removed(String::from("7")));
+        assert_eq!(changed[1], &DiffLine::Added(String::from("new")));
+    }
+
+    #[test]
+    fn score_details() {
+        let testcase = |outcome: &str, time| Testcase {
+            text: String::new(),
+            outcome: String::from(outcome),
+            time,
+            idx: Some(String::from("001")),
+            memory: 1024,
+        };
+        let subtask = |score, testcases| ScoreDetail {
+            testcases,
+            score,
+            max_score: 50.0,
+            idx: Some(1),
+        };
+        let a = vec![
+            subtask(50.0, vec![testcase("Correct", 0.5)]),
+            subtask(0.0, vec![testcase("Not correct", 0.1)]),
+        ];
+        let b = vec![
+            subtask(0.0, vec![testcase("Not correct", 0.2)]),
+            subtask(50.0, vec![testcase("Correct", 0.1)]),
+        ];
+        let res = compare_score_details(&a, &b);
+        assert_eq!(res.len(), 2);
+        assert!(res[0].regressed());
+        assert!(!res[1].regressed());
+        assert!(res[0].testcases[0].outcome_changed());
+        assert_eq!(res[1].testcases[0].time_difference(), Some(0.0));
+        assert_eq!(res[1].testcases[0].memory_difference(), Some(0));
+    }
+}
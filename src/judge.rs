@@ -0,0 +1,271 @@
+//! Common interface of judges, so that tools can work with any of them
+//!
+//! `Judge` is implemented by `Client` for CMSocial and by `CwsClient` for ContestWebServer
+//! contests, and can be implemented by test doubles.
+//! Functions here return the same errors as the ones of `Client`.
+
+use crate::cws::{self, CwsClient};
+use crate::{Client, File, Submission};
+
+/// Task description common to all judges
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct JudgeTask {
+    pub name: String,
+    pub title: String,
+    /// in seconds
+    pub time_limit: Option<f64>,
+    /// in MiB
+    pub memory_limit: Option<u16>,
+    /// names of the files to submit, like `sum.%l`
+    pub submission_format: Vec<String>,
+    pub statements: Vec<File>,
+    pub attachments: Vec<File>,
+}
+
+/// Operations of a judge
+///
+/// `File`s are identified by their `digest`, which is whatever the judge needs to
+/// download them: the digest on CMSocial, the path in the contest on ContestWebServer
+pub trait Judge {
+    /// get the names of the tasks
+    fn task_names(&self) -> Result<Vec<String>, u8>;
+    /// get a task
+    fn task(&self, name: &str) -> Result<JudgeTask, u8>;
+    /// submit a not output-only task, returns the id of the submission
+    ///
+    /// the language is recognized from `filename` or `text`, `Err(4)` if this fails
+    fn submit(&self, task_name: &str, text: &str, filename: &str) -> Result<usize, u8>;
+    /// get the ids of the submissions of the logged user on a task
+    fn submission_ids(&self, task_name: &str) -> Result<Vec<usize>, u8>;
+    /// get a submission of a task
+    fn submission(&self, task_name: &str, id: usize) -> Result<Submission, u8>;
+    /// download a file
+    fn download(&self, file: &File) -> Result<Vec<u8>, u8>;
+
+    /// get the best score of the logged user on a task
+    fn best_score(&self, task_name: &str) -> Result<f64, u8> {
+        let mut res: f64 = 0.0;
+        for id in self.submission_ids(task_name)? {
+            if let Some(score) = self.submission(task_name, id)?.score {
+                res = res.max(score);
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl Judge for Client {
+    fn task_names(&self) -> Result<Vec<String>, u8> {
+        Ok(self
+            .get_full_task_list("newest", None, None)?
+            .tasks
+            .into_iter()
+            .map(|t| t.name)
+            .collect())
+    }
+    fn task(&self, name: &str) -> Result<JudgeTask, u8> {
+        let task = self.get_task(name)?;
+        Ok(JudgeTask {
+            statements: task
+                .statements
+                .it
+                .iter()
+                .map(|digest| File {
+                    name: String::from("testo.pdf"),
+                    digest: digest.clone(),
+                })
+                .collect(),
+            attachments: task
+                .attachments
+                .iter()
+                .filter_map(|a| match a.as_slice() {
+                    [name, digest, ..] => Some(File {
+                        name: name.clone(),
+                        digest: digest.clone(),
+                    }),
+                    _ => None,
+                })
+                .collect(),
+            name: task.name,
+            title: task.title,
+            time_limit: task.time_limit,
+            memory_limit: task.memory_limit,
+            submission_format: task.submission_format,
+        })
+    }
+    fn submit(&self, task_name: &str, text: &str, filename: &str) -> Result<usize, u8> {
+        Ok(self.submit_file(task_name, text, filename, None)?.id)
+    }
+    fn submission_ids(&self, task_name: &str) -> Result<Vec<usize>, u8> {
+        Ok(self
+            .get_submission_list(task_name)?
+            .submissions
+            .iter()
+            .map(|s| s.id)
+            .collect())
+    }
+    fn submission(&self, _task_name: &str, id: usize) -> Result<Submission, u8> {
+        let sub = self.get_submission(id)?;
+        Ok(Submission {
+            files: sub.files,
+            compilation_outcome: sub.compilation_outcome,
+            task_id: sub.task_id,
+            timestamp: sub.timestamp,
+            evaluation_outcome: sub.evaluation_outcome,
+            score: sub.score,
+            id: sub.id,
+        })
+    }
+    fn download(&self, file: &File) -> Result<Vec<u8>, u8> {
+        self.get_file_bytes(file)
+    }
+    /// the submission list already has the scores, so this is a single request
+    fn best_score(&self, task_name: &str) -> Result<f64, u8> {
+        Ok(self.get_submission_list(task_name)?.best_score())
+    }
+}
+
+/// submissions on ContestWebServer have no files, task id and timestamp,
+/// these are left empty
+impl Judge for CwsClient {
+    fn task_names(&self) -> Result<Vec<String>, u8> {
+        self.get_task_list()
+    }
+    fn task(&self, name: &str) -> Result<JudgeTask, u8> {
+        let task = self.get_task(name)?;
+        let file = |kind: &str, file: &String| File {
+            name: file.clone(),
+            digest: format!("tasks/{}/{}/{}", name, kind, file),
+        };
+        Ok(JudgeTask {
+            statements: task
+                .statements
+                .iter()
+                .map(|s| file("statements", s))
+                .collect(),
+            attachments: task
+                .attachments
+                .iter()
+                .map(|a| file("attachments", a))
+                .collect(),
+            name: task.name,
+            title: task.title,
+            time_limit: None,
+            memory_limit: None,
+            submission_format: task.submission_format,
+        })
+    }
+    fn submit(&self, task_name: &str, text: &str, filename: &str) -> Result<usize, u8> {
+        CwsClient::submit(self, task_name, text, filename)
+    }
+    fn submission_ids(&self, task_name: &str) -> Result<Vec<usize>, u8> {
+        self.get_submission_ids(task_name)
+    }
+    fn submission(&self, task_name: &str, id: usize) -> Result<Submission, u8> {
+        let sub = self.get_submission(task_name, id)?;
+        let compilation_outcome = match sub.status {
+            cws::status::COMPILING => None,
+            cws::status::COMPILATION_FAILED => Some(String::from("fail")),
+            _ => Some(String::from("ok")),
+        };
+        Ok(Submission {
+            files: Vec::new(),
+            compilation_outcome,
+            task_id: 0,
+            timestamp: 0.0,
+            evaluation_outcome: sub.get_score().map(|_| String::from("ok")),
+            score: sub.get_score(),
+            id,
+        })
+    }
+    fn download(&self, file: &File) -> Result<Vec<u8>, u8> {
+        self.get_bytes(&file.digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// judge with a single task and fixed scores
+    struct FakeJudge {
+        scores: HashMap<usize, Option<f64>>,
+    }
+    impl Judge for FakeJudge {
+        fn task_names(&self) -> Result<Vec<String>, u8> {
+            Ok(vec![String::from("sum")])
+        }
+        fn task(&self, name: &str) -> Result<JudgeTask, u8> {
+            Ok(JudgeTask {
+                name: String::from(name),
+                title: String::from("Sum"),
+                time_limit: Some(1.0),
+                memory_limit: Some(256),
+                submission_format: vec![String::from("sum.%l")],
+                statements: Vec::new(),
+                attachments: Vec::new(),
+            })
+        }
+        fn submit(&self, _: &str, _: &str, _: &str) -> Result<usize, u8> {
+            Err(3)
+        }
+        fn submission_ids(&self, _: &str) -> Result<Vec<usize>, u8> {
+            Ok(self.scores.keys().cloned().collect())
+        }
+        fn submission(&self, _: &str, id: usize) -> Result<Submission, u8> {
+            Ok(Submission {
+                files: Vec::new(),
+                compilation_outcome: Some(String::from("ok")),
+                task_id: 1,
+                timestamp: 0.0,
+                evaluation_outcome: None,
+                score: *self.scores.get(&id).ok_or(3)?,
+                id,
+            })
+        }
+        fn download(&self, _: &File) -> Result<Vec<u8>, u8> {
+            Err(3)
+        }
+    }
+
+    fn best_scores(judge: &dyn Judge) -> Result<Vec<(String, f64)>, u8> {
+        judge
+            .task_names()?
+            .into_iter()
+            .map(|name| judge.best_score(&name).map(|score| (name, score)))
+            .collect()
+    }
+
+    #[test]
+    fn generic_tools() {
+        let judge = FakeJudge {
+            scores: vec![(1, Some(30.0)), (2, None), (3, Some(70.0))]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(
+            best_scores(&judge).unwrap(),
+            vec![(String::from("sum"), 70.0)]
+        );
+        assert_eq!(judge.task("sum").unwrap().memory_limit, Some(256));
+
+        // only the submission list is in the cassette
+        let sub = |id: usize, score: Option<f64>| {
+            serde_json::json!({"files": [], "compilation_outcome": "ok", "task_id": 1,
+                "timestamp": 0.0, "evaluation_outcome": null, "score": score, "id": id})
+        };
+        let list = serde_json::json!({"success": 1,
+            "submissions": [sub(3, Some(70.0)), sub(2, None), sub(1, Some(30.0))]});
+        let client = crate::cassette::replaying(
+            "user",
+            vec![crate::cassette::Interaction::new(
+                "submission",
+                serde_json::json!({"action":"list","task_name":"sum"}),
+                200,
+                list.to_string().as_bytes(),
+            )],
+        );
+        assert_eq!(Judge::best_score(&client, "sum"), Ok(70.0));
+    }
+}
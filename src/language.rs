@@ -0,0 +1,223 @@
+//! Programming languages accepted by the judge
+
+use crate::DetailedSubmission;
+use std::fmt;
+use std::path::Path;
+
+/// A language accepted by the judge
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    C11,
+    Cpp11,
+    Cpp14,
+    Cpp17,
+    CSharp,
+    Go,
+    Haskell,
+    Java,
+    Pascal,
+    Php,
+    Python2,
+    Python3,
+    PyPy3,
+    Rust,
+}
+
+/// all the languages, in the order used to guess the language of an extension
+pub const LANGUAGES: &[Language] = &[
+    Language::C11,
+    Language::Cpp17,
+    Language::Cpp14,
+    Language::Cpp11,
+    Language::CSharp,
+    Language::Go,
+    Language::Haskell,
+    Language::Java,
+    Language::Pascal,
+    Language::Php,
+    Language::Python3,
+    Language::Python2,
+    Language::PyPy3,
+    Language::Rust,
+];
+
+impl Language {
+    /// get the name used by the server, like `C++17 / g++`
+    pub fn server_name(self) -> &'static str {
+        match self {
+            Language::C11 => "C11 / gcc",
+            Language::Cpp11 => "C++11 / g++",
+            Language::Cpp14 => "C++14 / g++",
+            Language::Cpp17 => "C++17 / g++",
+            Language::CSharp => "C# / Mono",
+            Language::Go => "Go",
+            Language::Haskell => "Haskell / ghc",
+            Language::Java => "Java / JDK",
+            Language::Pascal => "Pascal / fpc",
+            Language::Php => "PHP",
+            Language::Python2 => "Python 2 / CPython",
+            Language::Python3 => "Python 3 / CPython",
+            Language::PyPy3 => "Python 3 / PyPy",
+            Language::Rust => "Rust",
+        }
+    }
+
+    /// get the language with the given server name
+    pub fn from_server_name(name: &str) -> Option<Self> {
+        LANGUAGES.iter().cloned().find(|l| l.server_name() == name)
+    }
+
+    /// get the extensions of source files, the first one is the usual one
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Language::C11 => &["c"],
+            Language::Cpp11 | Language::Cpp14 | Language::Cpp17 => &["cpp", "cc", "cxx", "c++"],
+            Language::CSharp => &["cs"],
+            Language::Go => &["go"],
+            Language::Haskell => &["hs"],
+            Language::Java => &["java"],
+            Language::Pascal => &["pas"],
+            Language::Php => &["php"],
+            Language::Python2 | Language::Python3 | Language::PyPy3 => &["py"],
+            Language::Rust => &["rs"],
+        }
+    }
+
+    /// get the usual extension of source files, like `cpp`
+    pub fn extension(self) -> &'static str {
+        self.extensions()[0]
+    }
+
+    /// get the language of an extension, the most recent version if many have it
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        LANGUAGES
+            .iter()
+            .cloned()
+            .find(|l| l.extensions().contains(&extension))
+    }
+
+    /// get the language of a file from its extension
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        Self::from_extension(Path::new(filename).extension()?.to_str()?)
+    }
+
+    /// get the language of a source file, looking at its content if the extension
+    /// is missing, unknown or shared by many languages
+    pub fn detect(filename: &str, content: &str) -> Option<Self> {
+        match Self::from_filename(filename) {
+            Some(Language::Python3) if is_python2(content) => Some(Language::Python2),
+            Some(l) => Some(l),
+            None => from_content(content),
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.server_name())
+    }
+}
+
+/// whether python code uses python 2 only syntax or functions
+fn is_python2(content: &str) -> bool {
+    content.lines().any(|l| {
+        let l = l.trim_start();
+        l.starts_with("print ") && !l.starts_with("print (")
+    }) || content.contains("raw_input(")
+        || content.contains("xrange(")
+}
+
+/// guess the language of a source from its content
+fn from_content(content: &str) -> Option<Language> {
+    let has = |markers: &[&str]| markers.iter().any(|m| content.contains(m));
+    let res = if has(&["#include"]) {
+        if has(&[
+            "iostream",
+            "bits/stdc++",
+            "using namespace",
+            "std::",
+            "<vector>",
+        ]) {
+            Language::Cpp17
+        } else {
+            Language::C11
+        }
+    } else if has(&["package main"]) {
+        Language::Go
+    } else if has(&["fn main"]) {
+        Language::Rust
+    } else if has(&["static void Main"]) {
+        Language::CSharp
+    } else if has(&["static void main"]) {
+        Language::Java
+    } else if has(&["<?php"]) {
+        Language::Php
+    } else if has(&["main :: IO", "main = do"]) {
+        Language::Haskell
+    } else if has(&["end."]) && has(&["begin", "BEGIN", "Begin"]) {
+        Language::Pascal
+    } else if has(&["def ", "import ", "input(", "print"]) {
+        if is_python2(content) {
+            Language::Python2
+        } else {
+            Language::Python3
+        }
+    } else {
+        return None;
+    };
+    Some(res)
+}
+
+impl DetailedSubmission {
+    /// get the language of the submission, from the server or from the file names
+    pub fn get_language(&self) -> Option<Language> {
+        self.language
+            .as_deref()
+            .and_then(Language::from_server_name)
+            .or_else(|| {
+                self.files
+                    .iter()
+                    .find_map(|f| Language::from_filename(&f.name))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        assert_eq!(Language::from_filename("sol.cpp"), Some(Language::Cpp17));
+        assert_eq!(Language::from_filename("a/b.c"), Some(Language::C11));
+        assert_eq!(Language::from_filename("sol"), None);
+        assert_eq!(
+            Language::from_server_name("Pascal / fpc"),
+            Some(Language::Pascal)
+        );
+        for l in LANGUAGES {
+            assert_eq!(Language::from_server_name(&l.to_string()), Some(*l));
+        }
+        assert_eq!(
+            Language::detect("sol.py", "n = int(raw_input())\nprint n\n"),
+            Some(Language::Python2)
+        );
+        assert_eq!(
+            Language::detect("sol.py", "print(int(input()) * 2)\n"),
+            Some(Language::Python3)
+        );
+        assert_eq!(
+            Language::detect("sol", "#include <cstdio>\nusing namespace std;\n"),
+            Some(Language::Cpp17)
+        );
+        assert_eq!(
+            Language::detect("sol.txt", "#include <stdio.h>\nint main(){}\n"),
+            Some(Language::C11)
+        );
+        assert_eq!(
+            Language::detect("sol", "program a;\nbegin\n  writeln(1);\nend.\n"),
+            Some(Language::Pascal)
+        );
+        assert_eq!(Language::detect("sol", ""), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! Leaderboards of regions, provinces, cities and institutes
+
+use crate::{Client, User};
+use std::collections::{HashMap, HashSet};
+
+/// Which part of `Institute` users are grouped by
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupLevel {
+    Region,
+    Province,
+    City,
+    Institute,
+}
+impl GroupLevel {
+    /// name of the group of a user at this level, `None` if the user did not set it
+    pub fn group_of(self, user: &User) -> Option<String> {
+        let inst = &user.institute;
+        match self {
+            GroupLevel::Region => inst.region.clone(),
+            GroupLevel::Province => inst.province.clone(),
+            GroupLevel::City => inst.city.clone(),
+            GroupLevel::Institute => inst.name.as_ref().map(|name| match &inst.city {
+                Some(city) => format!("{} ({})", name, city),
+                None => name.clone(),
+            }),
+        }
+    }
+}
+
+/// A group in a leaderboard
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Group {
+    pub name: String,
+    /// sum of the scores of the members
+    pub score: u64,
+    /// number of members
+    pub users: usize,
+    /// number of members who solved at least a task
+    pub active_users: usize,
+    /// best members as (username, score), best first
+    pub top_users: Vec<(String, u32)>,
+    /// number of tasks fully solved by at least one member,
+    /// `None` if the users did not have `scores`
+    pub tasks_solved: Option<usize>,
+}
+
+/// Groups sorted by decreasing score
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Leaderboard {
+    pub level: GroupLevel,
+    pub groups: Vec<Group>,
+}
+impl Leaderboard {
+    /// group users at the given level, keeping the best `top` users of each group
+    ///
+    /// users who did not set the group at that level are ignored
+    pub fn new(users: &[User], level: GroupLevel, top: usize) -> Self {
+        let mut members: HashMap<String, Vec<&User>> = HashMap::new();
+        for u in users {
+            if let Some(g) = level.group_of(u) {
+                members.entry(g).or_default().push(u);
+            }
+        }
+        let mut groups: Vec<Group> = members
+            .into_iter()
+            .map(|(name, mut us)| {
+                us.sort_by_key(|u| std::cmp::Reverse(u.score));
+                let tasks_solved = if us.iter().any(|u| u.scores.is_some()) {
+                    let solved: HashSet<&str> = us
+                        .iter()
+                        .flat_map(|u| u.scores.iter().flatten())
+                        .filter(|s| s.score >= 100.0)
+                        .map(|s| s.name.as_str())
+                        .collect();
+                    Some(solved.len())
+                } else {
+                    None
+                };
+                Group {
+                    name,
+                    score: us.iter().map(|u| u.score as u64).sum(),
+                    users: us.len(),
+                    active_users: us.iter().filter(|u| u.tasks_solved > 0).count(),
+                    top_users: us
+                        .iter()
+                        .take(top)
+                        .map(|u| (u.username.clone(), u.score))
+                        .collect(),
+                    tasks_solved,
+                }
+            })
+            .collect();
+        groups.sort_by(|a, b| b.score.cmp(&a.score).then(a.name.cmp(&b.name)));
+        Leaderboard { level, groups }
+    }
+}
+
+impl Client {
+    /// get the whole list of users, in reverse order of score
+    ///
+    /// this does as many `get_user_list` requests as needed to get all of them
+    pub fn get_all_users(&self) -> Result<Vec<User>, u8> {
+        const PAGE: usize = 100;
+        let mut res = Vec::new();
+        loop {
+            let mut page = self.get_user_list(res.len(), res.len() + PAGE)?;
+            if page.users.is_empty() {
+                break;
+            }
+            res.append(&mut page.users);
+            if res.len() >= page.num {
+                break;
+            }
+        }
+        Ok(res)
+    }
+
+    /// build the leaderboard of all users at the given level, keeping the best `top` users of each group
+    ///
+    /// if `with_tasks` is true, a `get_user` request is done for every active user
+    /// to count the tasks solved by each group
+    pub fn get_leaderboard(
+        &self,
+        level: GroupLevel,
+        top: usize,
+        with_tasks: bool,
+    ) -> Result<Leaderboard, u8> {
+        let mut users = self.get_all_users()?;
+        if with_tasks {
+            for u in users.iter_mut() {
+                if u.tasks_solved > 0 && level.group_of(u).is_some() {
+                    u.scores = self.get_user(&u.username)?.scores;
+                }
+            }
+        }
+        Ok(Leaderboard::new(&users, level, top))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(username: &str, region: &str, score: u32, solved: &[&str]) -> User {
+        let scores: Vec<_> = solved.iter().map(|name| (*name, 100.0)).collect();
+        let mut user = crate::compare::tests::user(username, &scores);
+        user.institute.region = Some(String::from(region));
+        user.tasks_solved = solved.len() as i32;
+        user.score = score;
+        user
+    }
+
+    #[test]
+    fn regions() {
+        let users = [
+            user("a", "Veneto", 300, &["x", "y"]),
+            user("b", "Veneto", 0, &[]),
+            user("c", "Lazio", 500, &["x"]),
+            user("d", "Veneto", 250, &["y", "z"]),
+        ];
+        let lb = Leaderboard::new(&users, GroupLevel::Region, 2);
+        assert_eq!(lb.groups.len(), 2);
+        let veneto = &lb.groups[0];
+        assert_eq!(veneto.name, "Veneto");
+        assert_eq!(veneto.score, 550);
+        assert_eq!(veneto.users, 3);
+        assert_eq!(veneto.active_users, 2);
+        assert_eq!(
+            veneto.top_users,
+            vec![(String::from("a"), 300), (String::from("d"), 250)]
+        );
+        assert_eq!(veneto.tasks_solved, Some(3));
+        assert!(Leaderboard::new(&users, GroupLevel::City, 2)
+            .groups
+            .is_empty());
+    }
+}
@@ -1,13 +1,14 @@
 //! # Examples:
 //! * Print list of people with highest number of fastest solutions
-//! ```
+//! ```no_run
+//! # use cms_tools::*;
 //! //make a client
 //! let client = Client::new(String::from("Gemmady"));
 //! let task_list = client.get_task_list(0, 1024, "", None, None).unwrap();
 //! let mut hm = std::collections::HashMap::<String, u32>::new();
 //! for i in task_list.tasks {
 //!     let best = client.get_stats(&i.name).unwrap().best;
-//!     if best.len() > 0 {
+//!     if !best.is_empty() {
 //!         let t = hm.entry(best[0].username.clone()).or_insert(0);
 //!         *t += 1;
 //!     }
@@ -19,7 +20,8 @@
 //! }
 //! ```
 //! * Resubmit all fastest solutions
-//! ```
+//! ```no_run
+//! # use cms_tools::*;
 //! let username = String::from("user");
 //! let password = "password";
 //! let mut client = Client::new(username.clone());
@@ -29,7 +31,10 @@
 //!     if sc.score == 100.0 {
 //!         println!("{} has score 100", sc.title);
 //!         let sub_list = client.get_submission_list(&sc.name).unwrap();
-//!         let best_sub = sub_list.get_fastest_high(&client).unwrap();
+//!         let best_sub = sub_list
+//!             .get_fastest_high(&client, Metric::MaxTime)
+//!             .unwrap()
+//!             .unwrap();
 //!         let files = &best_sub.files;
 //!         if files.len() == 1 { // if it is not an output-only
 //!             let mut submitted: bool = false;
@@ -37,17 +42,18 @@
 //!             while !submitted { // because cmsocial has a limit to submission rate
 //!                 print!(".");
 //!                 if client
-//!                     .submit_normal(
+//!                     .submit_file(
 //!                         &sc.name,
 //!                         &client.get_file(&files[0]).unwrap(),
-//!                         files[0].name.split(".").collect::<Vec<&str>>().last().unwrap(),
+//!                         &files[0].name,
+//!                         None,
 //!                     )
 //!                     .is_ok()
 //!                 {
 //!                     submitted = true;
 //!                 }
 //!             }
-//!             println!("");
+//!             println!();
 //!         }
 //!     }
 //! }
@@ -58,17 +64,57 @@
 //! * admin requests ([this](https://github.com/algorithm-ninja/cmsocial/blob/88bb6e8992455d2d780c33214e895d8d3f5e63ed/cmsocial-web/scripts/admin.js#L38))
 //! * any request I do not know the existence of
 
+pub mod analysis;
+pub mod archive;
+pub mod cassette;
+pub mod compare;
+pub mod cws;
+pub mod date;
+pub mod diagnostics;
+pub mod diff;
+pub mod judge;
+pub mod language;
+pub mod leaderboard;
+pub mod metrics;
+pub mod prometheus;
+pub mod query;
+pub mod ranking;
+pub mod recommend;
+pub mod retry;
+pub mod runner;
+pub mod schema;
+pub mod stress;
+pub mod training;
+pub mod watch;
+pub mod workspace;
+
+/// current unix timestamp, like the ones used by the server
+pub(crate) fn now() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
+/// splitmix64, a small generator of pseudo-random numbers
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
 // User management related
 
 /// Response for email and username checking
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct CheckResponse {
     pub success: u8,
     pub error: Option<String>,
 }
 
 /// Responses for password recovery requests
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct RecoverResponse {
     pub message: Option<String>,
     #[serde(rename = "type")]
@@ -80,7 +126,7 @@ pub struct RecoverResponse {
 // Users related
 
 /// Institute of an user
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Institute {
     pub province: Option<String>,
     pub city: Option<String>,
@@ -90,23 +136,36 @@ pub struct Institute {
 }
 
 /// User info
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct User {
     pub username: String,
+    #[serde(default)]
     pub mail_hash: String,
+    #[serde(default)]
     pub first_name: String,
+    #[serde(default)]
     pub last_name: String,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub institute: Institute,
+    #[serde(default)]
     pub tasks_solved: i32,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub access_level: u8,
+    #[serde(default)]
     pub join_date: f64,
+    #[serde(default)]
     pub score: u32,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub global_access_level: u8,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub scores: Option<Vec<Score>>,
+    /// fields of the response not in the model, not serialized
+    #[serde(flatten, skip_serializing)]
+    pub extra: schema::Extra,
 }
 
 /// List of users
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct UserList {
     pub num: usize,
     pub users: Vec<User>,
@@ -116,38 +175,50 @@ pub struct UserList {
 // Task related
 
 /// digest of statement file? apparently not
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Statement {
     pub it: Option<String>,
 }
 
 /// tags, like technique or event tags
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Tag {
     pub name: String,
     pub can_delete: bool,
 }
 
 /// Detailed task description
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct DetailedTask {
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub time_limit: Option<f64>,
+    #[serde(default)]
     pub help_available: bool,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub statements: Statement,
     pub name: String,
     pub success: u8,
+    #[serde(default)]
     pub title: String,
     pub submission_format: Vec<String>,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub memory_limit: Option<u16>,
+    #[serde(default)]
     pub task_type: String,
+    #[serde(default)]
     pub score_multiplier: f64,
     pub id: usize,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub tags: Vec<Tag>,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub attachments: Vec<Vec<String>>,
+    /// fields of the response not in the model, not serialized
+    #[serde(flatten, skip_serializing)]
+    pub extra: schema::Extra,
 }
 
 /// Task in a TaskList
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Task {
     pub score: Option<f64>,
     pub title: String,
@@ -157,7 +228,7 @@ pub struct Task {
 }
 
 /// List of tasks
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct TaskList {
     pub tasks: Vec<Task>,
     pub num: usize,
@@ -165,14 +236,14 @@ pub struct TaskList {
 }
 
 /// Best time by someone on some task
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Stat {
     pub username: String,
     pub time: f64,
 }
 
 /// Stats of a certain task
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Stats {
     pub nsubscorrect: usize,
     pub success: u8,
@@ -185,7 +256,7 @@ pub struct Stats {
 // Submission related
 
 /// Score achieved on a certain task by someone
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Score {
     pub score: f64,
     pub name: String,
@@ -193,14 +264,14 @@ pub struct Score {
 }
 
 /// Description of a file
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct File {
     pub name: String,
     pub digest: String,
 }
 
 /// Submission, not detailed
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Submission {
     pub files: Vec<File>,
     pub compilation_outcome: Option<String>,
@@ -212,7 +283,7 @@ pub struct Submission {
 }
 
 /// Outcome of a certain submission for a specific testcase
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Testcase {
     pub text: String,
     pub outcome: String,
@@ -222,7 +293,7 @@ pub struct Testcase {
 }
 
 /// Score details for a specific submission
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct ScoreDetail {
     pub testcases: Vec<Testcase>,
     pub score: f64,
@@ -231,22 +302,37 @@ pub struct ScoreDetail {
 }
 
 /// Details of a specific submission
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct DetailedSubmission {
+    #[serde(default)]
     pub files: Vec<File>,
+    #[serde(default)]
     pub compilation_outcome: Option<String>,
+    #[serde(default)]
     pub task_id: usize,
+    #[serde(default)]
     pub language: Option<String>,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub score_details: Option<Vec<ScoreDetail>>,
+    #[serde(default)]
     pub timestamp: f64,
+    #[serde(default)]
     pub compilation_stderr: Option<String>,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub compilation_time: Option<f64>,
+    #[serde(default)]
     pub evaluation_outcome: Option<String>,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub score: Option<f64>,
+    #[serde(default)]
     pub compilation_stdout: Option<String>,
     pub success: u8,
     pub id: usize,
+    #[serde(default, deserialize_with = "schema::or_default")]
     pub compilation_memory: Option<u64>,
+    /// fields of the response not in the model, not serialized
+    #[serde(flatten, skip_serializing)]
+    pub extra: schema::Extra,
 }
 impl DetailedSubmission {
     /// get the maximum execution time among all testcases
@@ -264,6 +350,15 @@ impl DetailedSubmission {
             _ => None,
         }
     }
+    /// get the sum of the execution times of all testcases
+    pub fn get_total_time(&self) -> Option<f64> {
+        self.score_details.as_ref().map(|sc| {
+            sc.iter()
+                .flat_map(|subtask| &subtask.testcases)
+                .map(|testcase| testcase.time)
+                .sum()
+        })
+    }
     /// get the maximum memory used among all testcases
     pub fn get_memory(&self) -> Option<u64> {
         match &self.score_details {
@@ -282,7 +377,7 @@ impl DetailedSubmission {
 }
 
 /// List of submissions by a user for a task
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct SubmissionList {
     pub submissions: Vec<Submission>,
     pub success: u8,
@@ -316,40 +411,77 @@ impl SubmissionList {
         if best_ind == self.submissions.len() {
             return None;
         }
-        return Some(&self.submissions[best_ind]);
+        Some(&self.submissions[best_ind])
     }
-    /// get the fastest submission among those with highest score
+    /// get the fastest submission among those with highest score, according to `metric`
     ///
-    /// in case of parity, the latest is returned
-    pub fn get_fastest_high(&self, client: &Client) -> Option<&Submission> {
-        let best_score: f64 = self.best_score();
-        let mut best_time: f64 = 100.0;
-        let mut best_ind: usize = self.submissions.len();
-        for i in 0..self.submissions.len() {
-            if let Some(score) = self.submissions[i].score {
-                if score == best_score {
-                    if let Ok(sub) = client.get_submission(self.submissions[i].id) {
-                        if let Some(time) = sub.get_time() {
-                            if time < best_time {
-                                best_time = time;
-                                best_ind = i;
-                            }
-                        }
-                    }
+    /// the details of the submissions are fetched concurrently,
+    /// in case of parity the latest is returned
+    pub fn get_fastest_high(
+        &self,
+        client: &Client,
+        metric: Metric,
+    ) -> Result<Option<DetailedSubmission>, u8> {
+        let best_score = self.best_score();
+        let ids: Vec<usize> = self
+            .submissions
+            .iter()
+            .filter(|s| s.score == Some(best_score))
+            .map(|s| s.id)
+            .collect();
+        Ok(metric.best(client.get_submissions(&ids)?))
+    }
+}
+
+/// How to compare the speed of submissions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// maximum execution time among all testcases
+    MaxTime,
+    /// sum of the execution times of all testcases
+    TotalTime,
+    /// maximum memory used among all testcases
+    MaxMemory,
+    /// maximum execution time, then maximum memory in case of parity
+    TimeThenMemory,
+}
+impl Metric {
+    /// get the value of a submission, lower is better
+    pub fn value(self, sub: &DetailedSubmission) -> Option<(f64, u64)> {
+        match self {
+            Metric::MaxTime => sub.get_time().map(|t| (t, 0)),
+            Metric::TotalTime => sub.get_total_time().map(|t| (t, 0)),
+            Metric::MaxMemory => sub.get_memory().map(|m| (0.0, m)),
+            Metric::TimeThenMemory => Some((sub.get_time()?, sub.get_memory()?)),
+        }
+    }
+    /// get the best submission, ignoring the ones without details
+    ///
+    /// in case of parity the latest is returned
+    pub fn best<I: IntoIterator<Item = DetailedSubmission>>(
+        self,
+        subs: I,
+    ) -> Option<DetailedSubmission> {
+        let mut res: Option<((f64, u64), DetailedSubmission)> = None;
+        for sub in subs {
+            if let Some(value) = self.value(&sub) {
+                let better = match &res {
+                    None => true,
+                    Some((v, s)) => value < *v || (value == *v && sub.timestamp > s.timestamp),
+                };
+                if better {
+                    res = Some((value, sub));
                 }
             }
         }
-        if best_ind == self.submissions.len() {
-            return None;
-        }
-        return Some(&self.submissions[best_ind]);
+        res.map(|(_, sub)| sub)
     }
 }
 
 // Test related
 
 /// Basic informations of a test
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct TestHead {
     pub max_score: u8,
     pub name: String,
@@ -357,7 +489,7 @@ pub struct TestHead {
 }
 
 /// Question inside of a test
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Question {
     pub max_score: u8,
     pub text: String,
@@ -368,7 +500,7 @@ pub struct Question {
 }
 
 /// Test, also known as Quiz
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Test {
     pub success: u8,
     pub name: String,
@@ -377,14 +509,14 @@ pub struct Test {
 }
 
 /// List of Tests
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct TestList {
     pub tests: Vec<TestHead>,
     pub success: u8,
 }
 
 /// Region
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Region {
     pub id: usize,
     pub name: String,
@@ -393,14 +525,14 @@ pub struct Region {
 // Misc
 
 /// List of regions
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct RegionList {
     pub regions: Vec<Region>,
     pub success: u8,
 }
 
 /// List of `technique` tags
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct TechniqueList {
     pub success: u8,
     pub tags: Vec<String>,
@@ -408,6 +540,9 @@ pub struct TechniqueList {
 
 // Client
 
+/// maximum number of requests made at the same time by `Client::get_submissions`
+const CONCURRENT_REQUESTS: usize = 8;
+
 /// **Client** you will do almost everything with
 pub struct Client {
     /// The reqwest client
@@ -416,12 +551,25 @@ pub struct Client {
     pub username: String,
     /// if client has token, this should be true and false otherwise
     pub logged: bool,
+    /// cassette used to record or replay requests
+    cassette: Option<std::sync::Arc<std::sync::Mutex<cassette::Player>>>,
+    /// drift found in the responses, if the client is lenient
+    schema_reports: Option<std::sync::Arc<std::sync::Mutex<Vec<schema::Report>>>>,
+    /// policy for retrying requests that fail for transient reasons
+    retry: retry::RetryPolicy,
+    /// hook told about every request
+    metrics: Option<std::sync::Arc<dyn metrics::Metrics>>,
+    /// cookies set by the last login, as a `Cookie` header
+    session: std::sync::Mutex<Option<String>>,
+    /// url the endpoints are relative to, the api of training.olinfo.it except in tests
+    base_url: String,
 }
 
 /// Client functions return:
 /// * `Err(1)` if request is not Ok, usually when server is unreachable
 /// * `Err(2)` when json cannot be parsed, usually unreachable or due to incorrect parameters
 /// * `Err(3)` if the json contains success=0
+/// * `Err(4)` if the language of a source file cannot be recognized, only by `submit_file`
 impl Client {
     // Client related
 
@@ -437,8 +585,195 @@ impl Client {
                 .cookie_store(true)
                 .build()
                 .unwrap(),
-            username: username,
+            username,
             logged: false,
+            cassette: None,
+            schema_reports: None,
+            retry: retry::RetryPolicy::never(),
+            metrics: None,
+            session: std::sync::Mutex::new(None),
+            base_url: String::from("https://training.olinfo.it/api/"),
+        }
+    }
+
+    /// create a client logged in with a session got from `Client::session`,
+    /// without using the password again
+    ///
+    /// `Err(2)` if `session` is not a valid cookie header.
+    /// The session is not checked, requests fail with `Err(3)` if it has expired
+    pub fn resume(username: String, session: &str) -> Result<Self, u8> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        let cookie = reqwest::header::HeaderValue::from_str(session).map_err(|_| 2)?;
+        headers.insert(reqwest::header::COOKIE, cookie);
+        let mut client = Self::new(username);
+        client.client = reqwest::Client::builder()
+            .referer(false)
+            .default_headers(headers)
+            .build()
+            .unwrap();
+        client.logged = true;
+        *client.session.lock().unwrap() = Some(String::from(session));
+        Ok(client)
+    }
+
+    /// get the cookies set by the last login, to log in again later with `Client::resume`
+    ///
+    /// `None` if the client did not log in or got no cookies, like when replaying a cassette
+    pub fn session(&self) -> Option<String> {
+        self.session.lock().unwrap().clone()
+    }
+
+    /// send a request to `<base_url>/<endpoint>`, posting `payload`
+    /// if it is not null, and get the status and body of the response
+    ///
+    /// requests are traced and told to the metrics hook, if any
+    fn request(&self, endpoint: &str, payload: &serde_json::Value) -> Result<(u16, Vec<u8>), u8> {
+        let action = payload["action"].as_str();
+        let span = tracing::info_span!(
+            "request",
+            endpoint,
+            action = action.unwrap_or(""),
+            status = tracing::field::Empty,
+            retries = tracing::field::Empty,
+            duration_ms = tracing::field::Empty,
+        );
+        let _enter = span.enter();
+        // only at debug level, since submissions have the whole sources
+        tracing::debug!(payload = %metrics::redact(payload), "sending request");
+        let start = std::time::Instant::now();
+        let mut retries = 0;
+        let res = self.send(endpoint, payload, &mut retries);
+        let duration = start.elapsed();
+        span.record("retries", retries);
+        span.record("duration_ms", duration.as_secs_f64() * 1000.0);
+        match &res {
+            Ok((status, _)) => {
+                span.record("status", status);
+            }
+            Err(_) => tracing::warn!("request failed"),
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.on_request(&metrics::RequestEvent {
+                endpoint: String::from(endpoint),
+                action: action.map(String::from),
+                status: res.as_ref().ok().map(|(status, _)| *status),
+                success: res
+                    .as_ref()
+                    .ok()
+                    .and_then(|(_, body)| metrics::success(body)),
+                retries,
+                duration,
+            });
+        }
+        res
+    }
+
+    /// send a request, going through the cassette, if any, and retrying following
+    /// the retry policy, `retries` is set to the number of retries
+    fn send(
+        &self,
+        endpoint: &str,
+        payload: &serde_json::Value,
+        retries: &mut u32,
+    ) -> Result<(u16, Vec<u8>), u8> {
+        if let Some(cassette) = &self.cassette {
+            let mut player = cassette.lock().unwrap();
+            if player.mode == cassette::Mode::Replay {
+                let interaction = player.replay(endpoint, payload).ok_or(1)?;
+                return Ok((interaction.status, interaction.body()));
+            }
+        }
+        let url = format!("{}{}", self.base_url, endpoint);
+        let login = endpoint == "user" && payload["action"] == "login";
+        let send = || -> Result<(u16, Vec<u8>), u8> {
+            let request = if payload.is_null() {
+                self.client.get(&url)
+            } else {
+                self.client.post(&url).json(payload)
+            };
+            let mut response = request.send().map_err(|_| 1)?;
+            let status = response.status().as_u16();
+            let cookies: Vec<String> = response
+                .cookies()
+                .map(|c| format!("{}={}", c.name(), c.value()))
+                .collect();
+            if login && !cookies.is_empty() {
+                *self.session.lock().unwrap() = Some(cookies.join("; "));
+            }
+            let mut body = Vec::new();
+            response.copy_to(&mut body).map_err(|_| 1)?;
+            Ok((status, body))
+        };
+        let submit = endpoint == "submission" && payload["action"] == "new";
+        let mut attempt = 1;
+        let (status, body) = loop {
+            let res = send();
+            let failure = match &res {
+                Ok((status, _)) => retry::Failure::from_status(*status),
+                Err(_) => Some(retry::Failure::Network),
+            };
+            match failure {
+                Some(failure) if self.retry.retries(failure, attempt, submit) => {
+                    let wait = self.retry.backoff(attempt, retry::random());
+                    tracing::debug!(attempt, ?failure, ?wait, "retrying request");
+                    std::thread::sleep(wait);
+                    attempt += 1;
+                    *retries += 1;
+                }
+                _ => break res?,
+            }
+        };
+        if let Some(cassette) = &self.cassette {
+            let interaction = cassette::Interaction::new(endpoint, payload.clone(), status, &body);
+            cassette.lock().unwrap().record(interaction);
+        }
+        Ok((status, body))
+    }
+
+    /// post `payload` to an api endpoint, like `task`, and parse the response,
+    /// reporting its differences with the model if the client is lenient
+    fn post<T: serde::Serialize + serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        payload: serde_json::Value,
+    ) -> Result<T, u8> {
+        let (_, body) = self.request(endpoint, &payload)?;
+        match &self.schema_reports {
+            Some(reports) => {
+                let response = serde_json::from_slice(&body).map_err(|_| 2)?;
+                self.parse_lenient(reports, endpoint, &payload, &response)
+            }
+            None => serde_json::from_slice(&body).map_err(|e| {
+                tracing::warn!(endpoint, error = %e, "cannot parse response");
+                2
+            }),
+        }
+    }
+
+    /// call an action of an api endpoint that has no method yet, like
+    /// `call("task", "stats", json!({"name":"ois_luck"}))`
+    ///
+    /// `payload` must serialize to an object or to null, the action is added to it.
+    /// `Err(3)` if the response contains success=0.
+    /// Responses are not parsed in lenient mode, use `Resp = serde_json::Value`
+    /// to get them as they are
+    pub fn call<Req: serde::Serialize, Resp: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        action: &str,
+        payload: Req,
+    ) -> Result<Resp, u8> {
+        let mut payload = match serde_json::to_value(payload).map_err(|_| 2)? {
+            serde_json::Value::Object(fields) => fields,
+            serde_json::Value::Null => serde_json::Map::new(),
+            _ => return Err(2),
+        };
+        payload.insert(String::from("action"), serde_json::json!(action));
+        let (_, body) = self.request(endpoint, &serde_json::Value::Object(payload))?;
+        let resp: serde_json::Value = serde_json::from_slice(&body).map_err(|_| 2)?;
+        match resp.get("success").and_then(|s| s.as_u64()) {
+            Some(1) | None => serde_json::from_value(resp).map_err(|_| 2),
+            _ => Err(3),
         }
     }
 
@@ -451,30 +786,20 @@ impl Client {
         if self.logged {
             return Ok(true);
         }
-        match self.client.post("https://training.olinfo.it/api/user").json(&serde_json::json!({"action":"login","keep_signed":"false","username":self.username,"password":password})).send() {
-            Ok(mut response) => {
-                #[derive(serde::Serialize, serde::Deserialize)]
-                struct Resp{
-                    success: u8
-                }
-                match response.json::<Resp>() {
-                    Ok(resp) => {
-                        match resp.success {
-                            1 => {
-                                self.logged = true;
-                                Ok(false)
-                            }
-                            _ => Err(3)
-                        }
-                    },
-                    _ => {
-                        Err(2)
-                    }
-                }
-            },
-            _ => {
-                Err(1)
+        #[derive(serde::Serialize, serde::Deserialize, Default)]
+        struct Resp {
+            success: u8,
+        }
+        let resp: Resp = self.post(
+            "user",
+            serde_json::json!({"action":"login","keep_signed":"false","username":self.username,"password":password}),
+        )?;
+        match resp.success {
+            1 => {
+                self.logged = true;
+                Ok(false)
             }
+            _ => Err(3),
         }
     }
 
@@ -484,20 +809,13 @@ impl Client {
     ///
     /// [example cms page](https://training.olinfo.it/#/forgot-account)
     pub fn recover(&self, email: &str, code: &str) -> Result<RecoverResponse, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/user")
-            .json(&serde_json::json!({"action":"recover","code":code,"email":email}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<RecoverResponse>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(resp),
-                    _ => Err(3),
-                },
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let resp: RecoverResponse = self.post(
+            "user",
+            serde_json::json!({"action":"recover","code":code,"email":email}),
+        )?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
         }
     }
 
@@ -505,25 +823,17 @@ impl Client {
     ///
     /// [example cms page](https://training.olinfo.it/#/user/Gemmady/edit)
     pub fn user_update(&self, email: &str, password: &str, old_password: &str) -> Result<(), u8> {
-        match self.client.post("https://training.olinfo.it/api/user").json(&serde_json::json!({"action":"update","email":email,"old_password":old_password,"password":password})).send() {
-            Ok(mut response) => {
-                #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
-                struct Resp {
-                    success: u8
-                }
-                match response.json::<Resp>() {
-                    Ok(resp) => {
-                        match resp.success {
-                            1 => Ok(()),
-                            _ => Err(3)
-                        }
-                    },
-                    _ => Err(2)
-                }
-            },
-            _ => {
-                Err(1)
-            }
+        #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+        struct Resp {
+            success: u8,
+        }
+        let resp: Resp = self.post(
+            "user",
+            serde_json::json!({"action":"update","email":email,"old_password":old_password,"password":password}),
+        )?;
+        match resp.success {
+            1 => Ok(()),
+            _ => Err(3),
         }
     }
 
@@ -531,36 +841,17 @@ impl Client {
     ///
     /// [example is `Username` input](https://training.olinfo.it/#/signup)
     pub fn check_username(&self, username: &str) -> Result<CheckResponse, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/check")
-            .json(&serde_json::json!({"type":"username","value":username}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<CheckResponse>() {
-                Ok(resp) => Ok(resp),
-                _ => Err(2),
-            },
-            _ => Err(1),
-        }
+        self.post(
+            "check",
+            serde_json::json!({"type":"username","value":username}),
+        )
     }
 
     /// check if email is valid, note: `Ok` does not mean email is valid
     ///
     /// [example is `E-mail address` input](https://training.olinfo.it/#/signup)
     pub fn check_email(&self, email: &str) -> Result<CheckResponse, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/check")
-            .json(&serde_json::json!({"type":"email","value":email}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<CheckResponse>() {
-                Ok(resp) => Ok(resp),
-                _ => Err(2),
-            },
-            _ => Err(1),
-        }
+        self.post("check", serde_json::json!({"type":"email","value":email}))
     }
 
     /// check if password is valid, note: this is done locally
@@ -578,29 +869,22 @@ impl Client {
     ///
     /// [example is `Username` input](https://training.olinfo.it/#/signup)
     pub fn user_exists(&self, username: &str) -> Result<bool, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/check")
-            .json(&serde_json::json!({"type":"username","value":username}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<CheckResponse>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(false),
-                    _ => match resp.error {
-                        Some(x) => {
-                            if x == String::from("This username is not available") {
-                                Ok(true)
-                            } else {
-                                Ok(false)
-                            }
-                        }
-                        _ => Err(3),
-                    },
-                },
-                _ => Err(2),
+        let resp: CheckResponse = self.post(
+            "check",
+            serde_json::json!({"type":"username","value":username}),
+        )?;
+        match resp.success {
+            1 => Ok(false),
+            _ => match resp.error {
+                Some(x) => {
+                    if x == "This username is not available" {
+                        Ok(true)
+                    } else {
+                        Ok(false)
+                    }
+                }
+                _ => Err(3),
             },
-            _ => Err(1),
         }
     }
 
@@ -608,20 +892,13 @@ impl Client {
     ///
     /// [example cms page](https://training.olinfo.it/#/ranking/1)
     pub fn get_user_list(&self, first: usize, last: usize) -> Result<UserList, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/user")
-            .json(&serde_json::json!({"action":"list","first":first,"last":last}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<UserList>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(resp),
-                    _ => Err(3),
-                },
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let resp: UserList = self.post(
+            "user",
+            serde_json::json!({"action":"list","first":first,"last":last}),
+        )?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
         }
     }
 
@@ -629,18 +906,10 @@ impl Client {
     ///
     /// [example cms page](https://training.olinfo.it/#/user/MyK_00L/profile)
     pub fn get_user(&self, username: &str) -> Result<User, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/user")
-            .json(&serde_json::json!({"action":"get","username":username}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<User>() {
-                Ok(resp) => Ok(resp),
-                _ => Err(2),
-            },
-            _ => Err(1),
-        }
+        self.post(
+            "user",
+            serde_json::json!({"action":"get","username":username}),
+        )
     }
 
     // Task related
@@ -660,42 +929,47 @@ impl Client {
         tag: Option<&str>,
         search: Option<&str>,
     ) -> Result<TaskList, u8> {
-        match self.client.post("https://training.olinfo.it/api/task").json(&serde_json::json!({"action":"list","first":first,"last":last,"order":order,"tag":tag,"search":search})).send() {
-            Ok(mut response) => {
-                match response.json::<TaskList>() {
-                    Ok(resp) => {
-                        match resp.success {
-                            1 => Ok(resp),
-                            _ => Err(3)
-                        }
-                    },
-                    _ => Err(2)
-                }
-            },
-            _ => {
-                Err(1)
+        let resp: TaskList = self.post(
+            "task",
+            serde_json::json!({"action":"list","first":first,"last":last,"order":order,"tag":tag,"search":search}),
+        )?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
+        }
+    }
+
+    /// get the whole list of tasks in the given order with the given tag that matches search
+    ///
+    /// this does as many `get_task_list` requests as needed to get all of them
+    pub fn get_full_task_list(
+        &self,
+        order: &str,
+        tag: Option<&str>,
+        search: Option<&str>,
+    ) -> Result<TaskList, u8> {
+        const PAGE: usize = 1024;
+        let mut res = self.get_task_list(0, PAGE, order, tag, search)?;
+        while res.tasks.len() < res.num {
+            let first = res.tasks.len();
+            let mut page = self.get_task_list(first, first + PAGE, order, tag, search)?;
+            if page.tasks.is_empty() {
+                break;
             }
+            res.tasks.append(&mut page.tasks);
         }
+        Ok(res)
     }
 
     /// get the details of a specific task
     ///
     /// [example cms page](https://training.olinfo.it/#/task/ois_luck/statement)
     pub fn get_task(&self, name: &str) -> Result<DetailedTask, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/task")
-            .json(&serde_json::json!({"action":"get","name":name}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<DetailedTask>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(resp),
-                    _ => Err(3),
-                },
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let resp: DetailedTask =
+            self.post("task", serde_json::json!({"action":"get","name":name}))?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
         }
     }
 
@@ -703,20 +977,10 @@ impl Client {
     ///
     /// [example cms page](https://training.olinfo.it/#/task/ois_luck/stats)
     pub fn get_stats(&self, name: &str) -> Result<Stats, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/task")
-            .json(&serde_json::json!({"action":"stats","name":name}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<Stats>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(resp),
-                    _ => Err(3),
-                },
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let resp: Stats = self.post("task", serde_json::json!({"action":"stats","name":name}))?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
         }
     }
 
@@ -726,20 +990,13 @@ impl Client {
     ///
     /// [example cms page](https://training.olinfo.it/#/task/preoii_piccioni/submissions)
     pub fn get_submission_list(&self, task_name: &str) -> Result<SubmissionList, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/submission")
-            .json(&serde_json::json!({"action":"list","task_name":task_name}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<SubmissionList>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(resp),
-                    _ => Err(3),
-                },
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let resp: SubmissionList = self.post(
+            "submission",
+            serde_json::json!({"action":"list","task_name":task_name}),
+        )?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
         }
     }
 
@@ -747,54 +1004,101 @@ impl Client {
     ///
     /// [example is clicking on submission id](https://training.olinfo.it/#/task/preoii_piccioni/submissions)
     pub fn get_submission(&self, id: usize) -> Result<DetailedSubmission, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/submission")
-            .json(&serde_json::json!({"action":"details","id":id}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<DetailedSubmission>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(resp),
-                    _ => Err(3),
-                },
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let resp: DetailedSubmission = self.post(
+            "submission",
+            serde_json::json!({"action":"details","id":id}),
+        )?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
+        }
+    }
+
+    /// get details for many submissions, in the same order as `ids`
+    ///
+    /// requests are made concurrently, the first error is returned
+    pub fn get_submissions(&self, ids: &[usize]) -> Result<Vec<DetailedSubmission>, u8> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
         }
+        let chunk = ids.len().div_ceil(CONCURRENT_REQUESTS);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = ids
+                .chunks(chunk)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|id| self.get_submission(*id))
+                            .collect::<Result<Vec<_>, u8>>()
+                    })
+                })
+                .collect();
+            let mut res = Vec::with_capacity(ids.len());
+            for handle in handles {
+                res.extend(handle.join().map_err(|_| 1)??);
+            }
+            Ok(res)
+        })
     }
 
     /// submit a not output-only task
     ///
-    /// lang should be the extension of the file (c, cpp, pas)
+    /// lang should be the extension of the file (c, cpp, pas), the server chooses
+    /// the language from it
     ///
     /// [example is clicking on `submit` button](https://training.olinfo.it/#/task/fpb/submissions)
+    #[deprecated(note = "use `submit_file`, which sends the language of the source")]
     pub fn submit_normal(
         &self,
         task_name: &str,
         text: &str,
         lang: &str,
     ) -> Result<DetailedSubmission, u8> {
-        match self.get_task(task_name.clone()) {
-            Ok(t) => {
-                match self.client.post("https://training.olinfo.it/api/submission").json(&serde_json::json!({"action":"new","files":{t.submission_format[0].as_str():{"data":base64::encode(&text),"filename":format!("ace.{}",lang)}},"task_name":task_name})).send() {
-                    Ok(mut response) => {
-                        match response.json::<DetailedSubmission>() {
-                            Ok(resp) => {
-                                match resp.success {
-                                    1 => Ok(resp),
-                                    _ => Err(3)
-                                }
-                            },
-                            _ => Err(2)
-                        }
-                    },
-                    _ => {
-                        Err(1)
-                    }
-                }
-            }
-            Err(t) => Err(t)
+        let t = self.get_task(task_name)?;
+        let resp: DetailedSubmission = self.post(
+            "submission",
+            serde_json::json!({"action":"new","files":{t.submission_format[0].as_str():{"data":base64::encode(text),"filename":format!("ace.{}",lang)}},"task_name":task_name}),
+        )?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
+        }
+    }
+
+    /// submit a not output-only task in `language`
+    ///
+    /// `filename` is the name of the source file, like `sol.cpp`. If `language` is `None`
+    /// it is recognized from the extension or from the content, so C++ sources are
+    /// submitted as C++17. The extension is added if it is missing and directories are removed.
+    /// `Err(4)` if `language` is `None` and the language cannot be recognized
+    pub fn submit_file(
+        &self,
+        task_name: &str,
+        text: &str,
+        filename: &str,
+        language: Option<language::Language>,
+    ) -> Result<DetailedSubmission, u8> {
+        let filename = std::path::Path::new(filename)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(filename);
+        let language = match language {
+            Some(language) => language,
+            None => language::Language::detect(filename, text).ok_or(4)?,
+        };
+        let filename = match language::Language::from_filename(filename) {
+            Some(_) => String::from(filename),
+            None => format!("{}.{}", filename, language.extension()),
+        };
+        let t = self.get_task(task_name)?;
+        let resp: DetailedSubmission = self.post(
+            "submission",
+            serde_json::json!({"action":"new","files":{t.submission_format[0].as_str():{"data":base64::encode(text),"filename":filename,"language":language.server_name()}},"task_name":task_name}),
+        )?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
         }
     }
 
@@ -804,20 +1108,10 @@ impl Client {
     ///
     /// [example cms page](https://training.olinfo.it/#/tests)
     pub fn get_test_list(&self) -> Result<TestList, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/test")
-            .json(&serde_json::json!({"action":"list"}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<TestList>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(resp),
-                    _ => Err(3),
-                },
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let resp: TestList = self.post("test", serde_json::json!({"action":"list"}))?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
         }
     }
 
@@ -825,43 +1119,27 @@ impl Client {
     ///
     /// [example cms page](https://training.olinfo.it/#/test/scolastiche2012_c)
     pub fn get_test(&self, test_name: &str) -> Result<Test, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/test")
-            .json(&serde_json::json!({"action":"get","test_name":test_name}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<Test>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(resp),
-                    _ => Err(3),
-                },
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let resp: Test = self.post(
+            "test",
+            serde_json::json!({"action":"get","test_name":test_name}),
+        )?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
         }
     }
 
-    /// Misc
+    // Misc
 
     /// get a list of the regions
     ///
     /// [example cms page](https://training.olinfo.it/#/signup)
     pub fn get_region_list(&self) -> Result<RegionList, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/location")
-            .json(&serde_json::json!({"action":"listregions"}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<RegionList>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(resp),
-                    _ => Err(3),
-                },
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let resp: RegionList =
+            self.post("location", serde_json::json!({"action":"listregions"}))?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
         }
     }
 
@@ -869,20 +1147,13 @@ impl Client {
     ///
     /// [example cms page](https://training.olinfo.it/#/tags/techniques)
     pub fn get_technique_list(&self) -> Result<TechniqueList, u8> {
-        match self
-            .client
-            .post("https://training.olinfo.it/api/tag")
-            .json(&serde_json::json!({"action":"list","filter":"techniques"}))
-            .send()
-        {
-            Ok(mut response) => match response.json::<TechniqueList>() {
-                Ok(resp) => match resp.success {
-                    1 => Ok(resp),
-                    _ => Err(3),
-                },
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let resp: TechniqueList = self.post(
+            "tag",
+            serde_json::json!({"action":"list","filter":"techniques"}),
+        )?;
+        match resp.success {
+            1 => Ok(resp),
+            _ => Err(3),
         }
     }
 
@@ -890,23 +1161,23 @@ impl Client {
     ///
     /// [example is an incorrect solution for missioni by Gemmady](https://training.olinfo.it/api/files/3ab02f1a746cc64fbc1fe846e46dd9e4dd2ca0e4/missioni.cpp)
     pub fn get_file(&self, file: &File) -> Result<String, u8> {
-        match self
-            .client
-            .get(
-                format!(
-                    "https://training.olinfo.it/api/files/{}/{}",
-                    file.digest, file.name
-                )
-                .as_str(),
-            )
-            .send()
-        {
-            Ok(mut response) => match response.text() {
-                Ok(resp) => Ok(resp),
-                _ => Err(2),
-            },
-            _ => Err(1),
+        let (_, body) = self.request(
+            &format!("files/{}/{}", file.digest, file.name),
+            &serde_json::Value::Null,
+        )?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// download file without interpreting it as text, use this for pdfs and archives
+    pub fn get_file_bytes(&self, file: &File) -> Result<Vec<u8>, u8> {
+        let (status, body) = self.request(
+            &format!("files/{}/{}", file.digest, file.name),
+            &serde_json::Value::Null,
+        )?;
+        if !(200..300).contains(&status) {
+            return Err(3);
         }
+        Ok(body)
     }
 }
 
@@ -923,7 +1194,7 @@ mod tests {
         let mut hm = std::collections::HashMap::<String, u32>::new();
         for i in task_list.tasks {
             let best = client.get_stats(&i.name).unwrap().best;
-            if best.len() > 0 {
+            if !best.is_empty() {
                 let t = hm.entry(best[0].username.clone()).or_insert(0);
                 *t += 1;
             }
@@ -935,6 +1206,7 @@ mod tests {
         }
     }
     #[test]
+    #[allow(deprecated)]
     fn it_works() {
         let mut m = Client::new(String::from("MyK_00L"));
         println!("\n\nLOGIN\n{:?}", m.login("w"));
@@ -981,13 +1253,149 @@ mod tests {
     fn my_test() {
         let mut m = Client::new(String::from("MyK_00L"));
         println!("{:?}", m.login("sure"));
-        let id = m
+        let sub = m
             .get_submission_list("tai_mle")
             .unwrap()
-            .get_fastest_high(&m)
+            .get_fastest_high(&m, Metric::MaxMemory)
             .unwrap()
-            .id;
-        let sub = m.get_submission(id).unwrap();
-        println!("{}: {}", id, sub.get_memory().unwrap());
+            .unwrap();
+        println!("{}: {}", sub.id, sub.get_memory().unwrap());
+    }
+
+    #[test]
+    fn metric_best() {
+        let sub = |id, timestamp, times: &[(f64, u64)]| DetailedSubmission {
+            files: Vec::new(),
+            compilation_outcome: Some(String::from("ok")),
+            task_id: 1,
+            language: None,
+            score_details: Some(vec![ScoreDetail {
+                testcases: times
+                    .iter()
+                    .map(|(time, memory)| Testcase {
+                        text: String::from("Output is correct"),
+                        outcome: String::from("Correct"),
+                        time: *time,
+                        idx: None,
+                        memory: *memory,
+                    })
+                    .collect(),
+                score: 100.0,
+                max_score: 100.0,
+                idx: None,
+            }]),
+            timestamp,
+            compilation_stderr: None,
+            compilation_time: None,
+            evaluation_outcome: Some(String::from("ok")),
+            score: Some(100.0),
+            compilation_stdout: None,
+            success: 1,
+            id,
+            compilation_memory: None,
+            extra: Default::default(),
+        };
+        let subs = vec![
+            sub(1, 100.0, &[(0.5, 10), (0.5, 10)]),
+            sub(2, 200.0, &[(0.1, 30), (0.6, 30)]),
+            sub(3, 300.0, &[(0.5, 20), (0.1, 20)]),
+        ];
+        let best = |m: Metric| m.best(subs.clone()).unwrap().id;
+        assert_eq!(best(Metric::MaxTime), 3);
+        assert_eq!(best(Metric::TotalTime), 3);
+        assert_eq!(best(Metric::MaxMemory), 1);
+        assert_eq!(best(Metric::TimeThenMemory), 1);
+        assert!(Metric::MaxTime.best(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn call() {
+        use cassette::Interaction;
+        let client = cassette::replaying(
+            "user",
+            vec![
+                Interaction::new(
+                    "task",
+                    serde_json::json!({"action":"stats","name":"ois_luck"}),
+                    200,
+                    br#"{"success": 1, "nsubs": 10, "nusers": 4, "nsubscorrect": 3,
+                        "nuserscorrect": 2, "best": []}"#,
+                ),
+                Interaction::new(
+                    "user",
+                    serde_json::json!({"action":"newaction"}),
+                    200,
+                    br#"{"success": 0, "error": "Unknown action"}"#,
+                ),
+            ],
+        );
+        #[derive(serde::Serialize)]
+        struct Req<'a> {
+            name: &'a str,
+        }
+        let stats: Stats = client
+            .call("task", "stats", Req { name: "ois_luck" })
+            .unwrap();
+        assert_eq!(stats.nsubs, 10);
+        let raw: serde_json::Value = client
+            .call("task", "stats", serde_json::json!({"name":"ois_luck"}))
+            .unwrap();
+        assert_eq!(raw["best"], serde_json::json!([]));
+        assert_eq!(
+            client.call::<_, serde_json::Value>("user", "newaction", ()),
+            Err(3)
+        );
+        assert_eq!(client.call::<_, Stats>("task", "stats", 1).err(), Some(2));
+    }
+
+    #[test]
+    fn submit() {
+        use cassette::Interaction;
+        use language::Language;
+        let task = DetailedTask {
+            name: String::from("ois_luck"),
+            submission_format: vec![String::from("luck.%l")],
+            success: 1,
+            ..Default::default()
+        };
+        let submission = |id, filename: &str, language: Option<Language>| {
+            let mut payload = serde_json::json!({"action":"new","task_name":"ois_luck",
+                "files":{"luck.%l":{"data":base64::encode("int main(){}"),"filename":filename}}});
+            if let Some(language) = language {
+                payload["files"]["luck.%l"]["language"] = language.server_name().into();
+            }
+            let sub = DetailedSubmission {
+                id,
+                success: 1,
+                ..Default::default()
+            };
+            let body = serde_json::to_vec(&sub).unwrap();
+            Interaction::new("submission", payload, 200, &body)
+        };
+        let client = cassette::replaying(
+            "user",
+            vec![
+                Interaction::new(
+                    "task",
+                    serde_json::json!({"action":"get","name":"ois_luck"}),
+                    200,
+                    &serde_json::to_vec(&task).unwrap(),
+                ),
+                submission(1, "ace.cpp", None),
+                submission(2, "sol.cpp", Some(Language::Cpp11)),
+                submission(3, "ace.cpp", Some(Language::Cpp17)),
+            ],
+        );
+        let submit = |filename, language| {
+            client
+                .submit_file("ois_luck", "int main(){}", filename, language)
+                .map(|s| s.id)
+        };
+        #[allow(deprecated)]
+        let legacy = client.submit_normal("ois_luck", "int main(){}", "cpp");
+        assert_eq!(legacy.unwrap().id, 1);
+        assert_eq!(submit("dir/sol.cpp", Some(Language::Cpp11)), Ok(2));
+        assert_eq!(submit("ace.cpp", None), Ok(3));
+        assert_eq!(submit("sol.txt", None), Err(4));
     }
 }
@@ -0,0 +1,192 @@
+//! Instrumentation of the requests to the api
+//!
+//! Every request made by a `Client` is traced with a `request` span, with the endpoint,
+//! the action, the status, the number of retries and the duration. The payload, with
+//! passwords and tokens redacted, is logged at debug level. A `Metrics` hook set with `Client::set_metrics` is also
+//! told about every request; `Counters` counts requests and errors per endpoint.
+
+use crate::Client;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// replacement for the redacted values
+pub const REDACTED: &str = "[redacted]";
+
+/// whether the value of a field of a payload must not be logged
+fn is_secret(key: &str) -> bool {
+    let key = key.to_lowercase();
+    key.contains("password") || key.contains("token")
+}
+
+/// get a copy of a payload with passwords and tokens redacted
+pub fn redact(payload: &Value) -> Value {
+    match payload {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| {
+                    let v = if is_secret(k) {
+                        Value::from(REDACTED)
+                    } else {
+                        redact(v)
+                    };
+                    (k.clone(), v)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        _ => payload.clone(),
+    }
+}
+
+/// get the `success` field of the body of a response, if it is a JSON object with it
+pub(crate) fn success(body: &[u8]) -> Option<bool> {
+    #[derive(serde::Deserialize)]
+    struct Success {
+        success: Option<u64>,
+    }
+    let parsed: Success = serde_json::from_slice(body).ok()?;
+    parsed.success.map(|s| s != 0)
+}
+
+/// A request made to the api
+#[derive(Debug, Clone)]
+pub struct RequestEvent {
+    /// path after `/api/`, like `task` or `files/<digest>/<name>`
+    pub endpoint: String,
+    pub action: Option<String>,
+    /// HTTP status code of the response, `None` if there was no response
+    pub status: Option<u16>,
+    /// `success` field of the response, `None` if there was no response or it has none
+    pub success: Option<bool>,
+    /// number of retries after the first attempt
+    pub retries: u32,
+    /// time taken, including retries
+    pub duration: Duration,
+}
+impl RequestEvent {
+    /// get the first part of the endpoint, like `files`, used to group requests
+    pub fn group(&self) -> &str {
+        self.endpoint.split('/').next().unwrap_or("")
+    }
+
+    /// whether the request failed, without a response, with an error status or
+    /// with success=0, which is how the api reports errors like a wrong password
+    pub fn failed(&self) -> bool {
+        self.status.is_none_or(|s| s >= 400) || self.success == Some(false)
+    }
+}
+
+/// Hook told about every request made by a client
+pub trait Metrics: Send + Sync {
+    fn on_request(&self, event: &RequestEvent);
+}
+
+impl<F: Fn(&RequestEvent) + Send + Sync> Metrics for F {
+    fn on_request(&self, event: &RequestEvent) {
+        self(event)
+    }
+}
+
+/// Counts of the requests to an endpoint
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EndpointCounts {
+    pub requests: u64,
+    /// requests without a response, with an error status or with success=0
+    pub errors: u64,
+    pub retries: u64,
+    /// total time taken by the requests
+    pub seconds: f64,
+}
+
+/// Metrics hook counting requests and errors per endpoint
+#[derive(Debug, Default)]
+pub struct Counters {
+    endpoints: Mutex<BTreeMap<String, EndpointCounts>>,
+}
+impl Counters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// get the counts of each endpoint, by the first part of the endpoint like `files`
+    pub fn snapshot(&self) -> BTreeMap<String, EndpointCounts> {
+        self.endpoints.lock().unwrap().clone()
+    }
+}
+impl Metrics for Counters {
+    fn on_request(&self, event: &RequestEvent) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let counts = endpoints.entry(String::from(event.group())).or_default();
+        counts.requests += 1;
+        if event.failed() {
+            counts.errors += 1;
+        }
+        counts.retries += event.retries as u64;
+        counts.seconds += event.duration.as_secs_f64();
+    }
+}
+
+impl Client {
+    /// tell `metrics` about every request from now on
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = Some(metrics);
+    }
+
+    /// stop telling the metrics hook about requests
+    pub fn remove_metrics(&mut self) {
+        self.metrics = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::{replaying, Interaction};
+
+    #[test]
+    fn counters() {
+        let payload = serde_json::json!({"action":"login","username":"user","password":"secret",
+            "nested":{"old_password":"old","Token":"abc"}});
+        assert_eq!(
+            redact(&payload),
+            serde_json::json!({"action":"login","username":"user","password":REDACTED,
+                "nested":{"old_password":REDACTED,"Token":REDACTED}})
+        );
+
+        let mut client = replaying(
+            "user",
+            vec![
+                Interaction::new(
+                    "task",
+                    serde_json::json!({"action":"stats","name":"ois_luck"}),
+                    200,
+                    br#"{"success": 0}"#,
+                ),
+                Interaction::new("files/abc/testo.pdf", Value::Null, 404, b"not found"),
+            ],
+        );
+        let counters = Arc::new(Counters::new());
+        client.set_metrics(counters.clone());
+        let file = crate::File {
+            name: String::from("testo.pdf"),
+            digest: String::from("abc"),
+        };
+        client.get_stats("ois_luck").unwrap_err();
+        client.get_stats("ois_sum").unwrap_err();
+        client.get_file_bytes(&file).unwrap_err();
+        let counts = counters.snapshot();
+        assert_eq!((counts["task"].requests, counts["task"].errors), (2, 2));
+        assert_eq!((counts["files"].requests, counts["files"].errors), (1, 1));
+
+        let actions = Arc::new(Mutex::new(Vec::new()));
+        let seen = actions.clone();
+        client.set_metrics(Arc::new(move |e: &RequestEvent| {
+            seen.lock().unwrap().push(e.action.clone())
+        }));
+        client.get_stats("ois_luck").unwrap_err();
+        assert_eq!(*actions.lock().unwrap(), vec![Some(String::from("stats"))]);
+    }
+}
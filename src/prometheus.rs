@@ -0,0 +1,373 @@
+//! Training progress of a team as Prometheus metrics
+//!
+//! Used by the `cms_exporter` binary, which serves them over HTTP in the
+//! Prometheus text format and collects them again every `ExporterConfig::interval` seconds.
+
+use crate::metrics::EndpointCounts;
+use crate::{Client, Stats, User};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Configuration of the exporter, missing fields take their default value
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ExporterConfig {
+    /// address the HTTP server listens on
+    pub listen: String,
+    /// seconds between two collections
+    pub interval: u64,
+    /// usernames of the team
+    pub users: Vec<String>,
+    /// names of the watched tasks
+    pub tasks: Vec<String>,
+    /// positions of the global ranking searched for the ranks of the users, 0 to not export ranks
+    pub rank_depth: usize,
+}
+impl Default for ExporterConfig {
+    fn default() -> Self {
+        ExporterConfig {
+            listen: String::from("127.0.0.1:9898"),
+            interval: 300,
+            users: Vec::new(),
+            tasks: Vec::new(),
+            rank_depth: 1000,
+        }
+    }
+}
+impl ExporterConfig {
+    /// parse a configuration in TOML format
+    pub fn from_toml(s: &str) -> Result<Self, u8> {
+        toml::from_str(s).map_err(|_| 2)
+    }
+    /// parse a configuration in JSON format
+    pub fn from_json(s: &str) -> Result<Self, u8> {
+        serde_json::from_str(s).map_err(|_| 2)
+    }
+    /// load a configuration from a file, it is parsed as JSON if the extension is `json` and as TOML otherwise
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, u8> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).map_err(|_| 1)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json(&s),
+            _ => Self::from_toml(&s),
+        }
+    }
+}
+
+/// A metric with all its samples
+#[derive(Debug, Clone)]
+pub struct MetricFamily {
+    pub name: String,
+    pub help: String,
+    /// `gauge` or `counter`
+    pub kind: &'static str,
+    /// values with their labels
+    pub samples: Vec<(Vec<(String, String)>, f64)>,
+}
+impl MetricFamily {
+    pub fn new(name: &str, help: &str, kind: &'static str) -> Self {
+        MetricFamily {
+            name: String::from(name),
+            help: String::from(help),
+            kind,
+            samples: Vec::new(),
+        }
+    }
+    /// add a sample with the given labels
+    pub fn add(&mut self, labels: &[(&str, &str)], value: f64) {
+        let labels = labels
+            .iter()
+            .map(|(k, v)| (String::from(*k), String::from(*v)))
+            .collect();
+        self.samples.push((labels, value));
+    }
+}
+
+fn escape_label(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_infinite() {
+        String::from(if value > 0.0 { "+Inf" } else { "-Inf" })
+    } else {
+        value.to_string()
+    }
+}
+
+/// render metrics in the Prometheus text format, skipping the ones without samples
+pub fn render(families: &[MetricFamily]) -> String {
+    let mut res = String::new();
+    for family in families.iter().filter(|f| !f.samples.is_empty()) {
+        let help = family.help.replace('\\', "\\\\").replace('\n', "\\n");
+        writeln!(res, "# HELP {} {}", family.name, help).unwrap();
+        writeln!(res, "# TYPE {} {}", family.name, family.kind).unwrap();
+        for (labels, value) in &family.samples {
+            res.push_str(&family.name);
+            if !labels.is_empty() {
+                let labels: Vec<String> = labels
+                    .iter()
+                    .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+                    .collect();
+                write!(res, "{{{}}}", labels.join(",")).unwrap();
+            }
+            writeln!(res, " {}", format_value(*value)).unwrap();
+        }
+    }
+    res
+}
+
+/// Data of a team collected for the exporter
+#[derive(Debug, Clone)]
+pub struct Progress {
+    /// unix timestamp of the collection
+    pub timestamp: f64,
+    pub users: Vec<User>,
+    /// ranks of the users found in the first `rank_depth` positions
+    pub ranks: HashMap<String, usize>,
+    /// statistics of the watched tasks, with their names
+    pub stats: Vec<(String, Stats)>,
+    /// number of failed requests
+    pub errors: usize,
+}
+impl Progress {
+    /// get the metrics of the team
+    pub fn families(&self) -> Vec<MetricFamily> {
+        let mut score = MetricFamily::new("cms_user_score", "total score of the user", "gauge");
+        let mut solved = MetricFamily::new(
+            "cms_user_tasks_solved",
+            "number of tasks solved by the user",
+            "gauge",
+        );
+        let mut rank = MetricFamily::new(
+            "cms_user_rank",
+            "position of the user in the global ranking",
+            "gauge",
+        );
+        let mut task_score = MetricFamily::new(
+            "cms_user_task_score",
+            "score of the user on a watched task",
+            "gauge",
+        );
+        for user in &self.users {
+            let username = user.username.as_str();
+            score.add(&[("username", username)], user.score as f64);
+            solved.add(&[("username", username)], user.tasks_solved as f64);
+            if let Some(r) = self.ranks.get(username) {
+                rank.add(&[("username", username)], *r as f64);
+            }
+            for s in user.scores.iter().flatten() {
+                if self.stats.iter().any(|(task, _)| *task == s.name) {
+                    task_score.add(&[("username", username), ("task", &s.name)], s.score);
+                }
+            }
+        }
+
+        let mut best = MetricFamily::new(
+            "cms_task_best_time_seconds",
+            "best time of all users on the task",
+            "gauge",
+        );
+        let mut user_best = MetricFamily::new(
+            "cms_task_user_best_time_seconds",
+            "time of the user on the task, if among the best times",
+            "gauge",
+        );
+        let mut users_solved = MetricFamily::new(
+            "cms_task_users_solved",
+            "number of users who solved the task",
+            "gauge",
+        );
+        let mut submissions = MetricFamily::new(
+            "cms_task_submissions",
+            "number of submissions on the task",
+            "gauge",
+        );
+        for (task, stats) in &self.stats {
+            let task = task.as_str();
+            if let Some(b) = stats.best.first() {
+                best.add(&[("task", task)], b.time);
+            }
+            for b in &stats.best {
+                if self.users.iter().any(|u| u.username == b.username) {
+                    user_best.add(&[("task", task), ("username", &b.username)], b.time);
+                }
+            }
+            users_solved.add(&[("task", task)], stats.nuserscorrect as f64);
+            submissions.add(&[("task", task)], stats.nsubs as f64);
+        }
+
+        let mut errors = MetricFamily::new(
+            "cms_collection_errors",
+            "number of failed requests in the last collection",
+            "gauge",
+        );
+        errors.add(&[], self.errors as f64);
+        let mut timestamp = MetricFamily::new(
+            "cms_collection_timestamp_seconds",
+            "unix timestamp of the last collection",
+            "gauge",
+        );
+        timestamp.add(&[], self.timestamp);
+        vec![
+            score,
+            solved,
+            rank,
+            task_score,
+            best,
+            user_best,
+            users_solved,
+            submissions,
+            errors,
+            timestamp,
+        ]
+    }
+}
+
+/// get the metrics of the requests made to the api, from `metrics::Counters`
+pub fn api_families(counts: &BTreeMap<String, EndpointCounts>) -> Vec<MetricFamily> {
+    let mut requests = MetricFamily::new(
+        "cms_api_requests_total",
+        "requests made to the api",
+        "counter",
+    );
+    let mut errors = MetricFamily::new(
+        "cms_api_errors_total",
+        "requests to the api without a response, with an error status or with success=0",
+        "counter",
+    );
+    let mut retries = MetricFamily::new(
+        "cms_api_retries_total",
+        "retries of requests to the api",
+        "counter",
+    );
+    let mut seconds = MetricFamily::new(
+        "cms_api_request_seconds_total",
+        "time spent in requests to the api",
+        "counter",
+    );
+    for (endpoint, c) in counts {
+        let labels = [("endpoint", endpoint.as_str())];
+        requests.add(&labels, c.requests as f64);
+        errors.add(&labels, c.errors as f64);
+        retries.add(&labels, c.retries as f64);
+        seconds.add(&labels, c.seconds);
+    }
+    vec![requests, errors, retries, seconds]
+}
+
+impl Client {
+    /// collect the data of the users and tasks in `config`, skipping the ones whose requests fail
+    pub fn collect_progress(&self, config: &ExporterConfig) -> Progress {
+        let mut res = Progress {
+            timestamp: crate::now(),
+            users: Vec::new(),
+            ranks: HashMap::new(),
+            stats: Vec::new(),
+            errors: 0,
+        };
+        for username in &config.users {
+            match self.get_user(username) {
+                Ok(user) => res.users.push(user),
+                Err(_) => res.errors += 1,
+            }
+        }
+        if config.rank_depth > 0 && !config.users.is_empty() {
+            match self.ranking_snapshot(0, config.rank_depth) {
+                Ok(snapshot) => {
+                    res.ranks = snapshot
+                        .entries
+                        .into_iter()
+                        .filter(|e| config.users.contains(&e.username))
+                        .map(|e| (e.username, e.rank))
+                        .collect()
+                }
+                Err(_) => res.errors += 1,
+            }
+        }
+        for task in &config.tasks {
+            match self.get_stats(task) {
+                Ok(stats) => res.stats.push((task.clone(), stats)),
+                Err(_) => res.errors += 1,
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::{replaying, user_response, Interaction};
+    use serde_json::json;
+
+    #[test]
+    fn exporter() {
+        let config = ExporterConfig::from_toml(
+            "users = [\"alice\", \"bob\"]\ntasks = [\"ois_luck\"]\nrank_depth = 100\n",
+        )
+        .unwrap();
+        assert_eq!(config.interval, 300);
+
+        let user =
+            |username: &str, score: u32| user_response(username, score, &[("ois_luck", 40.0)]);
+        let mut list = json!({"num": 2, "success": 1});
+        list["users"] = json!([user("carol", 300), user("alice", 100)]);
+        let client = replaying(
+            "user",
+            vec![
+                Interaction::new(
+                    "user",
+                    json!({"action":"get","username":"alice"}),
+                    200,
+                    user("alice", 100).to_string().as_bytes(),
+                ),
+                Interaction::new(
+                    "user",
+                    json!({"action":"list","first":0,"last":100}),
+                    200,
+                    list.to_string().as_bytes(),
+                ),
+                Interaction::new(
+                    "task",
+                    json!({"action":"stats","name":"ois_luck"}),
+                    200,
+                    br#"{"success": 1, "nsubs": 10, "nusers": 4, "nsubscorrect": 3,
+                        "nuserscorrect": 2, "best": [{"username": "carol", "time": 0.5},
+                        {"username": "alice", "time": 0.75}]}"#,
+                ),
+            ],
+        );
+        let progress = client.collect_progress(&config);
+        // bob is not in the cassette
+        assert_eq!(progress.errors, 1);
+        let text = render(&progress.families());
+        for line in &[
+            "# TYPE cms_user_score gauge",
+            "cms_user_score{username=\"alice\"} 100",
+            "cms_user_rank{username=\"alice\"} 2",
+            "cms_user_task_score{username=\"alice\",task=\"ois_luck\"} 40",
+            "cms_task_best_time_seconds{task=\"ois_luck\"} 0.5",
+            "cms_task_user_best_time_seconds{task=\"ois_luck\",username=\"alice\"} 0.75",
+            "cms_task_users_solved{task=\"ois_luck\"} 2",
+            "cms_collection_errors 1",
+        ] {
+            assert!(
+                text.lines().any(|l| l == *line),
+                "{} not in\n{}",
+                line,
+                text
+            );
+        }
+
+        let mut family = MetricFamily::new("x", "a\nb", "counter");
+        family.add(&[("l", "a\"b\\c")], f64::INFINITY);
+        let empty = MetricFamily::new("y", "", "gauge");
+        assert_eq!(
+            render(&[family, empty]),
+            "# HELP x a\\nb\n# TYPE x counter\nx{l=\"a\\\"b\\\\c\"} +Inf\n"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Filtering, sorting and summaries of submission lists
+
+use crate::language::Language;
+use crate::{Submission, SubmissionList};
+
+/// Outcome of a submission, as shown in the submission list
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    CompilationFailed,
+    /// still compiling or being evaluated
+    Pending,
+    /// score of 0
+    Zero,
+    Partial,
+    /// score of 100
+    Full,
+}
+
+/// get the outcome of a submission
+pub fn outcome(sub: &Submission) -> Outcome {
+    if sub.compilation_outcome.as_deref() == Some("fail") {
+        return Outcome::CompilationFailed;
+    }
+    match sub.score {
+        None => Outcome::Pending,
+        Some(s) if s >= 100.0 => Outcome::Full,
+        Some(s) if s > 0.0 => Outcome::Partial,
+        _ => Outcome::Zero,
+    }
+}
+
+/// get the language of a submission from the extension of its files,
+/// the most recent version if many languages have it, like C++17 for `cpp`
+pub fn language(sub: &Submission) -> Option<Language> {
+    sub.files
+        .iter()
+        .find_map(|f| Language::from_filename(&f.name))
+}
+
+/// Filter for submissions, `None` fields do not filter anything
+#[derive(Debug, Clone, Default)]
+pub struct SubmissionFilter {
+    /// minimum score, inclusive
+    pub min_score: Option<f64>,
+    /// maximum score, inclusive
+    pub max_score: Option<f64>,
+    pub outcomes: Option<Vec<Outcome>>,
+    /// languages, matched by extension since submission lists do not tell
+    /// versions like C++11 and C++17 apart
+    pub languages: Option<Vec<Language>>,
+    /// minimum timestamp, inclusive
+    pub since: Option<f64>,
+    /// maximum timestamp, exclusive
+    pub until: Option<f64>,
+}
+impl SubmissionFilter {
+    /// whether the submission passes the filter,
+    /// submissions without a score never pass a score range
+    pub fn matches(&self, sub: &Submission) -> bool {
+        if self.min_score.is_some() || self.max_score.is_some() {
+            match sub.score {
+                Some(s) => {
+                    if self.min_score.is_some_and(|m| s < m)
+                        || self.max_score.is_some_and(|m| s > m)
+                    {
+                        return false;
+                    }
+                }
+                None => return false,
+            }
+        }
+        if let Some(outcomes) = &self.outcomes {
+            if !outcomes.contains(&outcome(sub)) {
+                return false;
+            }
+        }
+        if let Some(languages) = &self.languages {
+            match language(sub) {
+                Some(l) if languages.iter().any(|x| x.extensions() == l.extensions()) => {}
+                _ => return false,
+            }
+        }
+        if self.since.is_some_and(|t| sub.timestamp < t)
+            || self.until.is_some_and(|t| sub.timestamp >= t)
+        {
+            return false;
+        }
+        true
+    }
+}
+
+/// What to sort submissions by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Timestamp,
+    /// submissions without a score come first
+    Score,
+    Id,
+}
+
+/// sort submissions by `key`, in decreasing order if `descending`
+pub fn sort_submissions(subs: &mut [&Submission], key: SortKey, descending: bool) {
+    subs.sort_by(|a, b| {
+        let ord = match key {
+            SortKey::Timestamp => a.timestamp.partial_cmp(&b.timestamp).unwrap(),
+            SortKey::Score => a
+                .score
+                .unwrap_or(-1.0)
+                .partial_cmp(&b.score.unwrap_or(-1.0))
+                .unwrap(),
+            SortKey::Id => a.id.cmp(&b.id),
+        };
+        if descending {
+            ord.reverse()
+        } else {
+            ord
+        }
+    });
+}
+
+/// Summary of the submissions of a user on a task
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SubmissionSummary {
+    pub attempts: usize,
+    pub best_score: f64,
+    /// timestamp of the first submission
+    pub first_attempt: Option<f64>,
+    /// number of submissions before the first one with full score
+    pub attempts_before_full: Option<usize>,
+    /// seconds from the first submission to the first one with full score
+    pub time_to_full: Option<f64>,
+    /// (timestamp, score) every time the best score improved, oldest first
+    pub progression: Vec<(f64, f64)>,
+}
+
+impl SubmissionList {
+    /// get the submissions passing the filter, in the same order as the list
+    pub fn filter(&self, filter: &SubmissionFilter) -> Vec<&Submission> {
+        self.submissions
+            .iter()
+            .filter(|s| filter.matches(s))
+            .collect()
+    }
+
+    /// get the submissions passing the filter, sorted by `key`
+    pub fn query(
+        &self,
+        filter: &SubmissionFilter,
+        key: SortKey,
+        descending: bool,
+    ) -> Vec<&Submission> {
+        let mut res = self.filter(filter);
+        sort_submissions(&mut res, key, descending);
+        res
+    }
+
+    /// summarize the submissions
+    pub fn summary(&self) -> SubmissionSummary {
+        let mut subs: Vec<&Submission> = self.submissions.iter().collect();
+        sort_submissions(&mut subs, SortKey::Timestamp, false);
+        let first_attempt = subs.first().map(|s| s.timestamp);
+        let mut res = SubmissionSummary {
+            attempts: subs.len(),
+            best_score: 0.0,
+            first_attempt,
+            attempts_before_full: None,
+            time_to_full: None,
+            progression: Vec::new(),
+        };
+        for (i, sub) in subs.iter().enumerate() {
+            if let Some(score) = sub.score {
+                if score > res.best_score {
+                    res.best_score = score;
+                    res.progression.push((sub.timestamp, score));
+                }
+                if score >= 100.0 && res.attempts_before_full.is_none() {
+                    res.attempts_before_full = Some(i);
+                    res.time_to_full = first_attempt.map(|t| sub.timestamp - t);
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::File;
+
+    fn sub(id: usize, timestamp: f64, score: Option<f64>, file: &str) -> Submission {
+        Submission {
+            files: vec![File {
+                name: String::from(file),
+                digest: String::new(),
+            }],
+            compilation_outcome: Some(String::from(if score.is_some() { "ok" } else { "fail" })),
+            task_id: 1,
+            timestamp,
+            evaluation_outcome: None,
+            score,
+            id,
+        }
+    }
+
+    #[test]
+    fn query_and_summary() {
+        let list = SubmissionList {
+            submissions: vec![
+                sub(5, 500.0, Some(100.0), "a.cpp"),
+                sub(4, 400.0, Some(100.0), "a.c"),
+                sub(3, 300.0, Some(30.0), "a.cpp"),
+                sub(2, 200.0, None, "a.cpp"),
+                sub(1, 100.0, Some(30.0), "a.pas"),
+            ],
+            success: 1,
+        };
+        let filter = SubmissionFilter {
+            languages: Some(vec![Language::Cpp11]),
+            ..Default::default()
+        };
+        let ids: Vec<usize> = list
+            .query(&filter, SortKey::Id, false)
+            .iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids, vec![2, 3, 5]);
+        let filter = SubmissionFilter {
+            min_score: Some(1.0),
+            max_score: Some(99.0),
+            since: Some(150.0),
+            ..Default::default()
+        };
+        assert_eq!(list.filter(&filter).len(), 1);
+        let filter = SubmissionFilter {
+            outcomes: Some(vec![Outcome::CompilationFailed]),
+            ..Default::default()
+        };
+        assert_eq!(list.filter(&filter)[0].id, 2);
+
+        let summary = list.summary();
+        assert_eq!(summary.attempts, 5);
+        assert_eq!(summary.attempts_before_full, Some(3));
+        assert_eq!(summary.time_to_full, Some(300.0));
+        assert_eq!(summary.progression, vec![(100.0, 30.0), (400.0, 100.0)]);
+    }
+}
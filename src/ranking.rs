@@ -0,0 +1,264 @@
+//! Snapshots of the global ranking and differences between them
+//!
+//! The site only shows the current ranking, snapshots can be saved periodically
+//! (e.g. in a `SnapshotStore`) to see how it changes over time.
+//!
+//! Functions saving or loading snapshots return:
+//! * `Err(1)` if a file cannot be read or written
+//! * `Err(2)` if a file cannot be parsed
+
+use crate::Client;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A user in a ranking snapshot
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct RankingEntry {
+    /// 1-based position in the ranking
+    pub rank: usize,
+    pub username: String,
+    pub score: u32,
+    pub tasks_solved: i32,
+}
+
+/// The ranking at a certain time
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RankingSnapshot {
+    /// unix timestamp of when the snapshot was taken
+    pub timestamp: f64,
+    pub entries: Vec<RankingEntry>,
+}
+impl RankingSnapshot {
+    /// save the snapshot as JSON
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), u8> {
+        let s = serde_json::to_string(self).map_err(|_| 2)?;
+        std::fs::write(path, s).map_err(|_| 1)
+    }
+    /// load a snapshot saved with `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, u8> {
+        let s = std::fs::read_to_string(path).map_err(|_| 1)?;
+        serde_json::from_str(&s).map_err(|_| 2)
+    }
+    /// get the differences from an older snapshot to this one
+    pub fn diff(&self, old: &RankingSnapshot) -> RankingDiff {
+        let old_entries: HashMap<&str, &RankingEntry> = old
+            .entries
+            .iter()
+            .map(|e| (e.username.as_str(), e))
+            .collect();
+        let mut changes = Vec::new();
+        let mut newcomers = Vec::new();
+        for e in &self.entries {
+            match old_entries.get(e.username.as_str()) {
+                Some(o) => changes.push(RankChange {
+                    username: e.username.clone(),
+                    old_rank: o.rank,
+                    new_rank: e.rank,
+                    rank_delta: o.rank as i64 - e.rank as i64,
+                    score_delta: e.score as i64 - o.score as i64,
+                    newly_solved: e.tasks_solved - o.tasks_solved,
+                }),
+                None => newcomers.push(e.clone()),
+            }
+        }
+        let new_usernames: std::collections::HashSet<&str> =
+            self.entries.iter().map(|e| e.username.as_str()).collect();
+        let dropped = old
+            .entries
+            .iter()
+            .filter(|e| !new_usernames.contains(e.username.as_str()))
+            .cloned()
+            .collect();
+        RankingDiff {
+            from: old.timestamp,
+            to: self.timestamp,
+            changes,
+            newcomers,
+            dropped,
+        }
+    }
+}
+
+/// Change of a user present in both snapshots
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct RankChange {
+    pub username: String,
+    pub old_rank: usize,
+    pub new_rank: usize,
+    /// positions gained, negative if the user went down
+    pub rank_delta: i64,
+    pub score_delta: i64,
+    pub newly_solved: i32,
+}
+
+/// Differences between two ranking snapshots
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RankingDiff {
+    /// timestamp of the older snapshot
+    pub from: f64,
+    /// timestamp of the newer snapshot
+    pub to: f64,
+    /// users in both snapshots, in the order of the newer one
+    pub changes: Vec<RankChange>,
+    /// users only in the newer snapshot
+    pub newcomers: Vec<RankingEntry>,
+    /// users only in the older snapshot, usually because they fell out of the snapshotted range
+    pub dropped: Vec<RankingEntry>,
+}
+impl RankingDiff {
+    /// get the `n` users who gained the most positions, ties broken by newly solved tasks
+    pub fn movers(&self, n: usize) -> Vec<&RankChange> {
+        let mut res: Vec<&RankChange> = self.changes.iter().filter(|c| c.rank_delta > 0).collect();
+        res.sort_by(|a, b| {
+            b.rank_delta
+                .cmp(&a.rank_delta)
+                .then(b.newly_solved.cmp(&a.newly_solved))
+        });
+        res.truncate(n);
+        res
+    }
+    /// get the `n` users who solved the most tasks between the snapshots
+    pub fn most_solved(&self, n: usize) -> Vec<&RankChange> {
+        let mut res: Vec<&RankChange> =
+            self.changes.iter().filter(|c| c.newly_solved > 0).collect();
+        res.sort_by_key(|c| std::cmp::Reverse(c.newly_solved));
+        res.truncate(n);
+        res
+    }
+}
+
+/// Directory of ranking snapshots, one JSON file per snapshot named after its timestamp
+pub struct SnapshotStore {
+    pub dir: PathBuf,
+}
+impl SnapshotStore {
+    /// use `dir` as store, creating it if needed
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self, u8> {
+        std::fs::create_dir_all(&dir).map_err(|_| 1)?;
+        Ok(SnapshotStore {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+    /// save a snapshot in the store
+    pub fn save(&self, snapshot: &RankingSnapshot) -> Result<(), u8> {
+        snapshot.save(self.dir.join(format!("{}.json", snapshot.timestamp as u64)))
+    }
+    /// get the timestamps of the snapshots in the store, oldest first
+    pub fn list(&self) -> Result<Vec<u64>, u8> {
+        let mut res: Vec<u64> = std::fs::read_dir(&self.dir)
+            .map_err(|_| 1)?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let path = e.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                path.file_stem()?.to_str()?.parse().ok()
+            })
+            .collect();
+        res.sort_unstable();
+        Ok(res)
+    }
+    /// load the snapshot with the given timestamp
+    pub fn load(&self, timestamp: u64) -> Result<RankingSnapshot, u8> {
+        RankingSnapshot::load(self.dir.join(format!("{}.json", timestamp)))
+    }
+    /// load the latest snapshot taken not after `timestamp`, if any
+    pub fn load_at(&self, timestamp: u64) -> Result<Option<RankingSnapshot>, u8> {
+        match self.list()?.into_iter().rev().find(|t| *t <= timestamp) {
+            Some(t) => self.load(t).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Client {
+    /// take a snapshot of the ranking in [first,last)
+    pub fn ranking_snapshot(&self, first: usize, last: usize) -> Result<RankingSnapshot, u8> {
+        const PAGE: usize = 100;
+        let timestamp = crate::now();
+        let mut entries = Vec::new();
+        let mut pos = first;
+        while pos < last {
+            let list = self.get_user_list(pos, last.min(pos + PAGE))?;
+            if list.users.is_empty() {
+                break;
+            }
+            for u in list.users {
+                pos += 1;
+                entries.push(RankingEntry {
+                    rank: pos,
+                    username: u.username,
+                    score: u.score,
+                    tasks_solved: u.tasks_solved,
+                });
+            }
+            if pos >= list.num {
+                break;
+            }
+        }
+        Ok(RankingSnapshot { timestamp, entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(rank: usize, username: &str, score: u32, tasks_solved: i32) -> RankingEntry {
+        RankingEntry {
+            rank,
+            username: String::from(username),
+            score,
+            tasks_solved,
+        }
+    }
+
+    #[test]
+    fn diff() {
+        let old = RankingSnapshot {
+            timestamp: 0.0,
+            entries: vec![
+                entry(1, "a", 300, 3),
+                entry(2, "b", 200, 2),
+                entry(3, "c", 100, 1),
+            ],
+        };
+        let new = RankingSnapshot {
+            timestamp: 1.0,
+            entries: vec![
+                entry(1, "b", 500, 5),
+                entry(2, "a", 300, 3),
+                entry(3, "d", 250, 2),
+            ],
+        };
+        let diff = new.diff(&old);
+        assert_eq!(diff.changes.len(), 2);
+        assert_eq!(diff.changes[0].rank_delta, 1);
+        assert_eq!(diff.changes[0].newly_solved, 3);
+        assert_eq!(diff.changes[1].rank_delta, -1);
+        assert_eq!(diff.newcomers, vec![entry(3, "d", 250, 2)]);
+        assert_eq!(diff.dropped, vec![entry(3, "c", 100, 1)]);
+        let movers = diff.movers(10);
+        assert_eq!(movers.len(), 1);
+        assert_eq!(movers[0].username, "b");
+    }
+
+    #[test]
+    fn store() {
+        let dir = std::env::temp_dir().join(format!("cms_tools_ranking_{}", std::process::id()));
+        let store = SnapshotStore::new(&dir).unwrap();
+        for t in &[20.0, 10.0] {
+            store
+                .save(&RankingSnapshot {
+                    timestamp: *t,
+                    entries: vec![entry(1, "a", 1, 1)],
+                })
+                .unwrap();
+        }
+        assert_eq!(store.list().unwrap(), vec![10, 20]);
+        assert_eq!(store.load_at(15).unwrap().unwrap().timestamp, 10.0);
+        assert!(store.load_at(5).unwrap().is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
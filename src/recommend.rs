@@ -0,0 +1,309 @@
+//! Task recommendations based on difficulty estimates
+//!
+//! The difficulty of a task is estimated from its `score_multiplier` and from the ratio
+//! of users who solved it among those who tried, and compared with the median
+//! `score_multiplier` of the tasks already solved by the user.
+//! Tasks with technique tags the user already solved something with are considered a bit easier,
+//! as are tasks the user already has a partial score on.
+
+use crate::{Client, Stats, Task};
+use std::collections::{HashMap, HashSet};
+
+/// how much harder than the user level recommended tasks should ideally be
+const STRETCH: f64 = 0.1;
+/// how much knowing all the techniques of a task lowers its estimated difficulty
+const TAG_WEIGHT: f64 = 0.25;
+/// how much a partial score of 100 would lower the estimated difficulty
+const PARTIAL_WEIGHT: f64 = 0.5;
+
+/// Options for `Client::recommend_tasks`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RecommendOptions {
+    /// number of tasks to recommend
+    pub count: usize,
+    /// only recommend tasks with all of these tags
+    pub tags: Vec<String>,
+    /// number of tasks to get stats for before choosing, at least `count`
+    pub candidates: usize,
+    /// get the technique tags of every task to account for the techniques known by the user,
+    /// this costs one request per technique
+    pub use_techniques: bool,
+}
+impl Default for RecommendOptions {
+    fn default() -> Self {
+        RecommendOptions {
+            count: 10,
+            tags: Vec::new(),
+            candidates: 30,
+            use_techniques: true,
+        }
+    }
+}
+
+/// A recommended task
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Recommendation {
+    pub name: String,
+    pub title: String,
+    pub score_multiplier: f64,
+    /// score the user already has on the task
+    pub score: f64,
+    /// ratio of users who solved the task among the ones who tried, if anyone tried
+    pub solve_ratio: Option<f64>,
+    /// technique tags of the task
+    pub tags: Vec<String>,
+    /// estimated difficulty relative to the user level, 0 is the level of the user,
+    /// positive is harder
+    pub difficulty: f64,
+    /// human readable reasons for the pick
+    pub explanation: String,
+}
+
+/// Data about an unsolved task used to estimate its difficulty
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub task: Task,
+    pub score: f64,
+    pub stats: Option<Stats>,
+    pub tags: Vec<String>,
+}
+
+/// Profile of what a user already solved
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// median score multiplier of the solved tasks
+    pub level: f64,
+    /// number of solved tasks
+    pub solved: usize,
+    /// number of solved tasks for each technique tag
+    pub tags: HashMap<String, usize>,
+}
+impl Profile {
+    /// build the profile given the solved tasks and the tags of every task
+    pub fn new(solved: &[&Task], task_tags: &HashMap<String, Vec<String>>) -> Self {
+        let mut multipliers: Vec<f64> = solved.iter().map(|t| t.score_multiplier).collect();
+        multipliers.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let level = if multipliers.is_empty() {
+            1.0
+        } else {
+            multipliers[multipliers.len() / 2]
+        };
+        let mut tags = HashMap::new();
+        for t in solved {
+            for tag in task_tags.get(&t.name).into_iter().flatten() {
+                *tags.entry(tag.clone()).or_insert(0) += 1;
+            }
+        }
+        Profile {
+            level,
+            solved: solved.len(),
+            tags,
+        }
+    }
+
+    /// preliminary difficulty estimate, only using data available in a task list
+    fn rough_difficulty(&self, task: &Task, score: f64) -> f64 {
+        (task.score_multiplier.max(1e-3) / self.level.max(1e-3)).ln()
+            - PARTIAL_WEIGHT * score / 100.0
+    }
+
+    /// estimate the difficulty of a candidate and explain it
+    pub fn evaluate(&self, c: Candidate) -> Recommendation {
+        let mut difficulty = self.rough_difficulty(&c.task, c.score);
+        let mut reasons = vec![format!(
+            "score multiplier {} (your median solved is {})",
+            c.task.score_multiplier, self.level
+        )];
+        let solve_ratio = match &c.stats {
+            Some(stats) if stats.nusers > 0 => {
+                let ratio = stats.nuserscorrect as f64 / stats.nusers as f64;
+                difficulty += 0.5 - ratio;
+                reasons.push(format!(
+                    "solved by {:.0}% of the {} users who tried",
+                    ratio * 100.0,
+                    stats.nusers
+                ));
+                Some(ratio)
+            }
+            _ => None,
+        };
+        if !c.tags.is_empty() {
+            let known: Vec<&String> = c
+                .tags
+                .iter()
+                .filter(|t| self.tags.contains_key(t.as_str()))
+                .collect();
+            difficulty -= TAG_WEIGHT * known.len() as f64 / c.tags.len() as f64;
+            if known.is_empty() {
+                reasons.push(format!("new techniques: {}", c.tags.join(", ")));
+            } else {
+                reasons.push(format!(
+                    "you already used {} of its techniques ({})",
+                    known.len(),
+                    known
+                        .iter()
+                        .map(|t| t.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ));
+            }
+        }
+        if c.score > 0.0 {
+            reasons.push(format!("you already have {} points on it", c.score));
+        }
+        Recommendation {
+            name: c.task.name,
+            title: c.task.title,
+            score_multiplier: c.task.score_multiplier,
+            score: c.score,
+            solve_ratio,
+            tags: c.tags,
+            difficulty,
+            explanation: reasons.join("; "),
+        }
+    }
+
+    /// evaluate all the candidates and keep the `count` closest to the ideal difficulty
+    pub fn rank(&self, candidates: Vec<Candidate>, count: usize) -> Vec<Recommendation> {
+        let mut res: Vec<Recommendation> =
+            candidates.into_iter().map(|c| self.evaluate(c)).collect();
+        res.sort_by(|a, b| {
+            (a.difficulty - STRETCH)
+                .abs()
+                .partial_cmp(&(b.difficulty - STRETCH).abs())
+                .unwrap()
+        });
+        res.truncate(count);
+        res
+    }
+}
+
+impl Client {
+    /// recommend tasks that `username` has not solved yet, closest to their level first
+    pub fn recommend_tasks(
+        &self,
+        username: &str,
+        options: &RecommendOptions,
+    ) -> Result<Vec<Recommendation>, u8> {
+        let user = self.get_user(username)?;
+        let tasks = self.get_full_task_list("", None, None)?.tasks;
+
+        let mut allowed: Option<HashSet<String>> = None;
+        for tag in &options.tags {
+            let with_tag: HashSet<String> = self
+                .get_full_task_list("", Some(tag), None)?
+                .tasks
+                .into_iter()
+                .map(|t| t.name)
+                .collect();
+            allowed = Some(match allowed {
+                Some(a) => a.intersection(&with_tag).cloned().collect(),
+                None => with_tag,
+            });
+        }
+
+        let mut task_tags: HashMap<String, Vec<String>> = HashMap::new();
+        if options.use_techniques {
+            for tag in self.get_technique_list()?.tags {
+                for t in self.get_full_task_list("", Some(&tag), None)?.tasks {
+                    task_tags.entry(t.name).or_default().push(tag.clone());
+                }
+            }
+        }
+
+        let scores: HashMap<String, f64> = user
+            .scores
+            .unwrap_or_default()
+            .into_iter()
+            .map(|s| (s.name, s.score))
+            .collect();
+        let score_of = |t: &Task| *scores.get(&t.name).unwrap_or(&0.0);
+        let solved: Vec<&Task> = tasks.iter().filter(|t| score_of(t) >= 100.0).collect();
+        let profile = Profile::new(&solved, &task_tags);
+
+        let mut unsolved: Vec<&Task> = tasks
+            .iter()
+            .filter(|t| score_of(t) < 100.0)
+            .filter(|t| allowed.as_ref().is_none_or(|a| a.contains(&t.name)))
+            .collect();
+        unsolved.sort_by(|a, b| {
+            let da = (profile.rough_difficulty(a, score_of(a)) - STRETCH).abs();
+            let db = (profile.rough_difficulty(b, score_of(b)) - STRETCH).abs();
+            da.partial_cmp(&db).unwrap()
+        });
+        unsolved.truncate(options.candidates.max(options.count));
+
+        let mut candidates = Vec::new();
+        for t in unsolved {
+            candidates.push(Candidate {
+                task: t.clone(),
+                score: score_of(t),
+                stats: Some(self.get_stats(&t.name)?),
+                tags: task_tags.get(&t.name).cloned().unwrap_or_default(),
+            });
+        }
+        Ok(profile.rank(candidates, options.count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(name: &str, score_multiplier: f64) -> Task {
+        Task {
+            score: None,
+            title: String::from(name),
+            score_multiplier,
+            id: 0,
+            name: String::from(name),
+        }
+    }
+
+    fn stats(nusers: usize, nuserscorrect: usize) -> Option<Stats> {
+        Some(Stats {
+            nsubscorrect: 0,
+            success: 1,
+            nusers,
+            nsubs: 0,
+            nuserscorrect,
+            best: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn ranking() {
+        let solved = [task("a", 1.0), task("b", 2.0), task("c", 2.0)];
+        let mut task_tags = HashMap::new();
+        task_tags.insert(String::from("b"), vec![String::from("dp")]);
+        let profile = Profile::new(&solved.iter().collect::<Vec<&Task>>(), &task_tags);
+        assert_eq!(profile.level, 2.0);
+        assert_eq!(profile.tags["dp"], 1);
+
+        let candidates = vec![
+            Candidate {
+                task: task("too_hard", 8.0),
+                score: 0.0,
+                stats: stats(100, 2),
+                tags: vec![String::from("flow")],
+            },
+            Candidate {
+                task: task("right", 2.0),
+                score: 0.0,
+                stats: stats(100, 40),
+                tags: vec![String::from("dp")],
+            },
+            Candidate {
+                task: task("too_easy", 0.5),
+                score: 50.0,
+                stats: stats(100, 95),
+                tags: Vec::new(),
+            },
+        ];
+        let res = profile.rank(candidates, 2);
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].name, "right");
+        assert_eq!(res[0].solve_ratio, Some(0.4));
+        assert!(res[0].explanation.contains("dp"));
+    }
+}
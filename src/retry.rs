@@ -0,0 +1,207 @@
+//! Retrying requests to the api that fail for transient reasons
+//!
+//! A client does not retry by default, `Client::set_retry_policy` makes it retry
+//! with an exponential backoff. Submissions are never retried unless
+//! `RetryPolicy::retry_submit` is set, since a retried submission may be submitted twice.
+
+use crate::Client;
+use std::time::Duration;
+
+/// A transient failure of a request
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// the request could not be sent or the response could not be read, like `Err(1)`
+    Network,
+    /// the server answered with a 5xx status
+    ServerError,
+    /// the server answered with 429 Too Many Requests
+    TooManyRequests,
+}
+impl Failure {
+    /// get the failure indicated by a status code, if any
+    pub fn from_status(status: u16) -> Option<Self> {
+        match status {
+            429 => Some(Failure::TooManyRequests),
+            500..=599 => Some(Failure::ServerError),
+            _ => None,
+        }
+    }
+}
+
+/// Policy for retrying requests
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// wait after the first failed attempt
+    pub initial_backoff: Duration,
+    /// factor multiplying the wait after each failed attempt
+    pub multiplier: f64,
+    pub max_backoff: Duration,
+    /// fraction between 0 and 1, waits are randomly shortened by up to this fraction
+    pub jitter: f64,
+    /// failures that are retried
+    pub retry_on: Vec<Failure>,
+    /// retry submissions too
+    pub retry_submit: bool,
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            multiplier: 2.0,
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.5,
+            retry_on: vec![
+                Failure::Network,
+                Failure::ServerError,
+                Failure::TooManyRequests,
+            ],
+            retry_submit: false,
+        }
+    }
+}
+impl RetryPolicy {
+    /// policy that never retries, used by default by clients
+    pub fn never() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// whether to retry after the failed attempt number `attempt`, starting from 1
+    pub fn retries(&self, failure: Failure, attempt: u32, submit: bool) -> bool {
+        attempt < self.max_attempts
+            && (!submit || self.retry_submit)
+            && self.retry_on.contains(&failure)
+    }
+
+    /// get the wait after the failed attempt number `attempt`, starting from 1,
+    /// `random` is a number between 0 and 1 used for the jitter
+    pub fn backoff(&self, attempt: u32, random: f64) -> Duration {
+        let wait = self.initial_backoff.as_secs_f64()
+            * self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        let wait = wait.min(self.max_backoff.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0) * random.clamp(0.0, 1.0);
+        Duration::from_secs_f64(wait * (1.0 - jitter))
+    }
+}
+
+/// random number between 0 and 1 for the jitter
+pub(crate) fn random() -> f64 {
+    let mut state = crate::now().to_bits();
+    (crate::splitmix64(&mut state) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl Client {
+    /// set the policy for retrying requests that fail for transient reasons
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    /// get the policy for retrying requests
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// answer the requests to `listener` with the given statuses, repeating the last one,
+    /// returns the number of requests received so far
+    fn serve(listener: TcpListener, statuses: Vec<(u16, &'static str)>) -> Arc<AtomicUsize> {
+        let count = Arc::new(AtomicUsize::new(0));
+        let received = count.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line.trim() != "" {
+                    let lower = line.to_lowercase();
+                    if let Some(value) = lower.strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    line.clear();
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                let i = received.fetch_add(1, Ordering::SeqCst);
+                let (status, body) = statuses[i.min(statuses.len() - 1)];
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        count
+    }
+
+    #[test]
+    fn retries_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = Client::new(String::from("user"));
+        client.base_url = format!("http://{}/api/", listener.local_addr().unwrap());
+        client.set_retry_policy(RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        });
+        let stats = r#"{"success": 1, "nsubs": 10, "nusers": 4, "nsubscorrect": 3,
+            "nuserscorrect": 2, "best": []}"#;
+        let count = serve(listener, vec![(503, "busy"), (200, stats), (503, "busy")]);
+        assert_eq!(client.get_stats("ois_luck").unwrap().nsubs, 10);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        let submission: Result<serde_json::Value, u8> = client.call(
+            "submission",
+            "new",
+            serde_json::json!({"task_name":"ois_luck"}),
+        );
+        assert_eq!(submission, Err(2));
+        // a submission is not retried
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert!(client
+            .call::<_, serde_json::Value>("task", "list", ())
+            .is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 3 + 4);
+    }
+
+    #[test]
+    fn policy() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(3),
+            retry_on: vec![Failure::Network, Failure::ServerError],
+            ..Default::default()
+        };
+        assert!(policy.retries(Failure::Network, 1, false));
+        assert!(policy.retries(Failure::ServerError, 2, false));
+        assert!(!policy.retries(Failure::ServerError, 3, false));
+        assert!(!policy.retries(Failure::TooManyRequests, 1, false));
+        assert!(!policy.retries(Failure::Network, 1, true));
+        assert!(!RetryPolicy::never().retries(Failure::Network, 1, false));
+        assert_eq!(policy.backoff(1, 0.0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2, 0.0), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3, 0.0), Duration::from_secs(3));
+        assert_eq!(policy.backoff(2, 1.0), Duration::from_secs(1));
+        for _ in 0..10 {
+            let r = random();
+            assert!((0.0..1.0).contains(&r));
+        }
+        assert_eq!(Failure::from_status(503), Some(Failure::ServerError));
+        assert_eq!(Failure::from_status(429), Some(Failure::TooManyRequests));
+        assert_eq!(Failure::from_status(404), None);
+    }
+}
@@ -0,0 +1,607 @@
+//! Run solutions locally on the sample testcases of a task before submitting them
+//!
+//! Sources are compiled with the same flags used by the judge, then run on every sample
+//! found in the task directory under the time and memory limits of the task.
+//! Results are reported as `Testcase`s, like the ones of a `DetailedSubmission`.
+//!
+//! `TaskWorkspace::submit_checked` submits a solution only if it passes the samples.
+//!
+//! Samples are pairs of files in the task directory (or in archives attachments, which are
+//! extracted with `unzip`) named like `input0.txt`/`output0.txt` or `name.in`/`name.out`.
+//!
+//! Functions here return:
+//! * `Err(1)` if a file cannot be read or written or a program cannot be started
+//! * `Err(4)` if the language of the source is not supported
+
+use crate::language::Language;
+use crate::workspace::TaskWorkspace;
+use crate::{Client, DetailedSubmission, Testcase};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// directory where programs are compiled and run, inside the task directory
+pub const BUILD_DIR: &str = ".build";
+/// directory where archive attachments are extracted, inside the task directory
+pub const SAMPLES_DIR: &str = "samples";
+
+/// A sample testcase
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+/// A compiled program, ready to be run
+#[derive(Debug, Clone)]
+pub struct Program {
+    /// command line to run the program
+    pub command: Vec<String>,
+}
+
+/// Result of compiling a source
+#[derive(Debug, Clone)]
+pub struct Compilation {
+    /// `None` if compilation failed
+    pub program: Option<Program>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Result of a single run of a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    /// judge-like outcome text if the program did not terminate correctly
+    pub failure: Option<String>,
+    /// in seconds
+    pub time: f64,
+    /// peak memory in bytes, 0 if it cannot be measured
+    pub memory: u64,
+}
+
+/// Options for running the samples
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// compare outputs exactly instead of ignoring whitespace differences
+    pub exact: bool,
+    /// used if the task has no time limit, in seconds
+    pub default_time_limit: f64,
+}
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            exact: false,
+            default_time_limit: 1.0,
+        }
+    }
+}
+
+/// Result of running a solution on the samples
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    pub compilation: Compilation,
+    pub testcases: Vec<Testcase>,
+}
+impl RunReport {
+    /// whether the solution compiled and all samples were correct
+    pub fn passed(&self) -> bool {
+        self.compilation.program.is_some() && self.testcases.iter().all(|t| t.outcome == "Correct")
+    }
+}
+
+/// Result of `TaskWorkspace::submit_checked`
+#[derive(Debug, Clone)]
+pub enum CheckedSubmission {
+    /// the solution was submitted
+    Submitted {
+        /// `None` if the samples were not run
+        report: Option<RunReport>,
+        submission: Box<DetailedSubmission>,
+    },
+    /// the solution failed on the samples and was not submitted
+    Refused(RunReport),
+}
+
+fn strings(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| String::from(*s)).collect()
+}
+
+/// compile `source` into `build_dir` with the flags used by the judge
+pub fn compile(source: &Path, build_dir: &Path) -> Result<Compilation, u8> {
+    std::fs::create_dir_all(build_dir).map_err(|_| 1)?;
+    let src = source.to_str().ok_or(1)?;
+    let exe_path = build_dir.join("solution");
+    let exe = exe_path.to_str().ok_or(1)?;
+    let content = std::fs::read_to_string(source).map_err(|_| 1)?;
+    let language = Language::detect(src, &content).ok_or(4)?;
+    let (compile, run): (Option<Vec<String>>, Vec<String>) = match language {
+        Language::C11 => (
+            Some(strings(&[
+                "gcc",
+                "-DEVAL",
+                "-std=gnu11",
+                "-O2",
+                "-pipe",
+                "-static",
+                "-s",
+                "-o",
+                exe,
+                src,
+                "-lm",
+            ])),
+            strings(&[exe]),
+        ),
+        Language::Cpp11 | Language::Cpp14 | Language::Cpp17 => (
+            Some(strings(&[
+                "g++",
+                "-DEVAL",
+                match language {
+                    Language::Cpp11 => "-std=gnu++11",
+                    Language::Cpp14 => "-std=gnu++14",
+                    _ => "-std=gnu++17",
+                },
+                "-O2",
+                "-pipe",
+                "-static",
+                "-s",
+                "-o",
+                exe,
+                src,
+            ])),
+            strings(&[exe]),
+        ),
+        Language::Pascal => (
+            Some(vec![
+                String::from("fpc"),
+                String::from("-dEVAL"),
+                String::from("-XS"),
+                String::from("-O2"),
+                format!("-o{}", exe),
+                String::from(src),
+            ]),
+            strings(&[exe]),
+        ),
+        Language::Python2 => (None, strings(&["python2", src])),
+        Language::Python3 => (None, strings(&["python3", src])),
+        _ => return Err(4),
+    };
+    match compile {
+        Some(cmd) => {
+            let out = Command::new(&cmd[0])
+                .args(&cmd[1..])
+                .output()
+                .map_err(|_| 1)?;
+            Ok(Compilation {
+                program: if out.status.success() {
+                    Some(Program { command: run })
+                } else {
+                    None
+                },
+                stdout: String::from_utf8_lossy(&out.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&out.stderr).into_owned(),
+            })
+        }
+        None => Ok(Compilation {
+            program: Some(Program { command: run }),
+            stdout: String::new(),
+            stderr: String::new(),
+        }),
+    }
+}
+
+/// peak memory used by a running process in bytes, only available on linux
+fn peak_memory(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// run `program` with `input` as stdin writing stdout to `output`,
+/// under the given time limit in seconds and memory limit in MiB
+pub fn run(
+    program: &Program,
+    input: &Path,
+    output: &Path,
+    time_limit: f64,
+    memory_limit: Option<u16>,
+) -> Result<Execution, u8> {
+    let stdin = std::fs::File::open(input).map_err(|_| 1)?;
+    let stdout = std::fs::File::create(output).map_err(|_| 1)?;
+    let mut cmd = match memory_limit {
+        #[cfg(unix)]
+        Some(mb) => {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c")
+                .arg(format!("ulimit -v {} && exec \"$@\"", mb as u64 * 1024))
+                .arg("sh")
+                .args(&program.command);
+            cmd
+        }
+        _ => {
+            let mut cmd = Command::new(&program.command[0]);
+            cmd.args(&program.command[1..]);
+            cmd
+        }
+    };
+    let start = Instant::now();
+    let mut child = cmd
+        .stdin(Stdio::from(stdin))
+        .stdout(Stdio::from(stdout))
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|_| 1)?;
+    // wall time is used, with some slack since it is usually higher than cpu time
+    let deadline = Duration::from_secs_f64(time_limit * 1.5 + 0.5);
+    let mut memory = 0;
+    loop {
+        if let Some(status) = child.try_wait().map_err(|_| 1)? {
+            let time = start.elapsed().as_secs_f64();
+            if time > time_limit {
+                return Ok(Execution {
+                    failure: Some(String::from("Execution timed out")),
+                    time,
+                    memory,
+                });
+            }
+            if status.success() {
+                return Ok(Execution {
+                    failure: None,
+                    time,
+                    memory,
+                });
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::ExitStatusExt;
+                if let Some(signal) = status.signal() {
+                    return Ok(Execution {
+                        failure: Some(format!(
+                            "Execution killed with signal {} (could be triggered by violating memory limits)",
+                            signal
+                        )),
+                        time,
+                        memory,
+                    });
+                }
+            }
+            return Ok(Execution {
+                failure: Some(String::from(
+                    "Execution failed because the return code was nonzero",
+                )),
+                time,
+                memory,
+            });
+        }
+        if let Some(m) = peak_memory(child.id()) {
+            memory = memory.max(m);
+        }
+        if start.elapsed() > deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(Execution {
+                failure: Some(String::from(
+                    "Execution timed out (wall clock limit exceeded)",
+                )),
+                time: start.elapsed().as_secs_f64(),
+                memory,
+            });
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// compare outputs, ignoring differences in whitespace unless `exact` is true
+pub fn same_output(expected: &str, got: &str, exact: bool) -> bool {
+    if exact {
+        expected == got
+    } else {
+        expected.split_whitespace().eq(got.split_whitespace())
+    }
+}
+
+/// get the name of the testcase if `file` is an input file, along with possible output file names
+fn output_names(file: &str) -> Option<(String, Vec<String>)> {
+    if let Some(rest) = file.strip_prefix("input") {
+        return Some((
+            String::from(rest.split('.').next().unwrap_or(rest)),
+            vec![format!("output{}", rest)],
+        ));
+    }
+    if let Some(stem) = file.strip_suffix(".in") {
+        return Some((
+            String::from(stem),
+            vec![
+                format!("{}.out", stem),
+                format!("{}.ans", stem),
+                format!("{}.sol", stem),
+            ],
+        ));
+    }
+    None
+}
+
+/// find the samples in a directory and its subdirectories, sorted by name
+pub fn find_samples(dir: &Path) -> Vec<Sample> {
+    let mut res = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(d) = dirs.pop() {
+        let entries = match std::fs::read_dir(&d) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                let name = entry.file_name();
+                if name != BUILD_DIR && name != crate::workspace::SUBMISSIONS_DIR {
+                    dirs.push(path);
+                }
+                continue;
+            }
+            let file = entry.file_name().to_string_lossy().into_owned();
+            if let Some((name, outputs)) = output_names(&file) {
+                if let Some(output) = outputs.iter().map(|o| d.join(o)).find(|o| o.is_file()) {
+                    res.push(Sample {
+                        name,
+                        input: path,
+                        output,
+                    });
+                }
+            }
+        }
+    }
+    res.sort_by(|a, b| a.name.cmp(&b.name).then(a.input.cmp(&b.input)));
+    res
+}
+
+impl TaskWorkspace {
+    /// extract the zip attachments in the samples directory, if not already done
+    pub fn extract_samples(&self) -> Result<(), u8> {
+        for path in self.attachment_paths() {
+            if path.extension().and_then(|e| e.to_str()) != Some("zip") {
+                continue;
+            }
+            let stem = path.file_stem().unwrap_or_default();
+            let target = self.dir.join(SAMPLES_DIR).join(stem);
+            if target.exists() {
+                continue;
+            }
+            std::fs::create_dir_all(&target).map_err(|_| 1)?;
+            let status = Command::new("unzip")
+                .arg("-o")
+                .arg("-q")
+                .arg(&path)
+                .arg("-d")
+                .arg(&target)
+                .status()
+                .map_err(|_| 1)?;
+            if !status.success() {
+                return Err(1);
+            }
+        }
+        Ok(())
+    }
+
+    /// get the samples of the task
+    pub fn samples(&self) -> Result<Vec<Sample>, u8> {
+        self.extract_samples()?;
+        Ok(find_samples(&self.dir))
+    }
+
+    /// compile `source` and run it on all the samples of the task
+    pub fn test_solution(&self, source: &Path, options: &RunOptions) -> Result<RunReport, u8> {
+        let build = self.dir.join(BUILD_DIR);
+        let compilation = compile(source, &build)?;
+        let mut testcases = Vec::new();
+        if let Some(program) = &compilation.program {
+            let time_limit = self
+                .metadata
+                .time_limit
+                .unwrap_or(options.default_time_limit);
+            for sample in self.samples()? {
+                let output = build.join("output.txt");
+                let execution = run(
+                    program,
+                    &sample.input,
+                    &output,
+                    time_limit,
+                    self.metadata.memory_limit,
+                )?;
+                let (outcome, text) = match execution.failure {
+                    Some(text) => (String::from("Not correct"), text),
+                    None => {
+                        let expected = std::fs::read_to_string(&sample.output).map_err(|_| 1)?;
+                        let got = std::fs::read_to_string(&output).unwrap_or_default();
+                        if same_output(&expected, &got, options.exact) {
+                            (String::from("Correct"), String::from("Output is correct"))
+                        } else {
+                            (
+                                String::from("Not correct"),
+                                String::from("Output isn't correct"),
+                            )
+                        }
+                    }
+                };
+                testcases.push(Testcase {
+                    text,
+                    outcome,
+                    time: execution.time,
+                    idx: Some(sample.name.clone()),
+                    memory: execution.memory,
+                });
+            }
+        }
+        Ok(RunReport {
+            compilation,
+            testcases,
+        })
+    }
+
+    /// run `source` on the samples and submit it with `client` only if it passes them,
+    /// or without running it if `force` is true
+    ///
+    /// `language` is the one given to `Client::submit_file`, whose errors are returned too
+    pub fn submit_checked(
+        &self,
+        client: &Client,
+        source: &Path,
+        language: Option<Language>,
+        options: &RunOptions,
+        force: bool,
+    ) -> Result<CheckedSubmission, u8> {
+        let report = if force {
+            None
+        } else {
+            let report = self.test_solution(source, options)?;
+            if !report.passed() {
+                return Ok(CheckedSubmission::Refused(report));
+            }
+            Some(report)
+        };
+        let text = std::fs::read_to_string(source).map_err(|_| 1)?;
+        let filename = source.to_str().ok_or(1)?;
+        let submission = client.submit_file(&self.metadata.name, &text, filename, language)?;
+        Ok(CheckedSubmission::Submitted {
+            report,
+            submission: Box::new(submission),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outputs() {
+        assert!(same_output("1 2\n3\n", "1  2 3", false));
+        assert!(!same_output("1 2\n3\n", "1  2 3", true));
+        assert!(!same_output("1 2", "1 2 3", false));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn limits() {
+        let dir = std::env::temp_dir().join(format!("cms_tools_run_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.txt");
+        let output = dir.join("output.txt");
+        std::fs::write(&input, "hello\n").unwrap();
+        let cat = Program {
+            command: strings(&["cat"]),
+        };
+        let failure = run(&cat, &input, &output, 1.0, Some(64)).unwrap().failure;
+        assert_eq!(failure, None);
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "hello\n");
+        let sleep = Program {
+            command: strings(&["sleep", "5"]),
+        };
+        let failure = run(&sleep, &input, &output, 0.1, None).unwrap().failure;
+        assert!(failure.unwrap().starts_with("Execution timed out"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn samples() {
+        let dir = std::env::temp_dir().join(format!("cms_tools_runner_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        for f in &[
+            "input0.txt",
+            "output0.txt",
+            "input1.txt",
+            "sub/a.in",
+            "sub/a.ans",
+        ] {
+            std::fs::write(dir.join(f), "").unwrap();
+        }
+        let samples = find_samples(&dir);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].name, "0");
+        assert_eq!(samples[1].name, "a");
+        assert_eq!(samples[1].output, dir.join("sub/a.ans"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn submit_checked() {
+        use crate::cassette::{replaying, Interaction};
+        use crate::workspace::TaskMetadata;
+        use crate::DetailedTask;
+        let dir = std::env::temp_dir().join(format!("cms_tools_checked_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let task = DetailedTask {
+            name: String::from("ois_luck"),
+            submission_format: vec![String::from("luck.%l")],
+            success: 1,
+            ..Default::default()
+        };
+        let ws = TaskWorkspace {
+            dir: dir.clone(),
+            metadata: TaskMetadata::new(&task),
+        };
+        std::fs::write(dir.join("input0.txt"), "1\n").unwrap();
+        std::fs::write(dir.join("output0.txt"), "1\n").unwrap();
+        let right = dir.join("right.py");
+        let wrong = dir.join("wrong.py");
+        let text = |path: &Path| std::fs::read_to_string(path).unwrap();
+        std::fs::write(&right, "print(input())\n").unwrap();
+        std::fs::write(&wrong, "print(2)\n").unwrap();
+        let submission = |id, path: &Path| {
+            let payload = serde_json::json!({"action":"new","task_name":"ois_luck",
+                "files":{"luck.%l":{"data":base64::encode(&text(path)),
+                "filename":path.file_name().unwrap().to_str().unwrap(),
+                "language":Language::Python3.server_name()}}});
+            let sub = DetailedSubmission {
+                id,
+                success: 1,
+                ..Default::default()
+            };
+            Interaction::new(
+                "submission",
+                payload,
+                200,
+                &serde_json::to_vec(&sub).unwrap(),
+            )
+        };
+        let client = replaying(
+            "user",
+            vec![
+                Interaction::new(
+                    "task",
+                    serde_json::json!({"action":"get","name":"ois_luck"}),
+                    200,
+                    &serde_json::to_vec(&task).unwrap(),
+                ),
+                submission(1, &right),
+                submission(2, &wrong),
+            ],
+        );
+        let options = RunOptions::default();
+        let submit = |path: &Path, force| {
+            ws.submit_checked(&client, path, None, &options, force)
+                .unwrap()
+        };
+        match submit(&wrong, false) {
+            CheckedSubmission::Refused(report) => assert_eq!(report.testcases.len(), 1),
+            other => panic!("submitted {:?}", other),
+        }
+        match submit(&right, false) {
+            CheckedSubmission::Submitted { report, submission } => {
+                assert!(report.unwrap().passed());
+                assert_eq!(submission.id, 1);
+            }
+            other => panic!("refused {:?}", other),
+        }
+        match submit(&wrong, true) {
+            CheckedSubmission::Submitted { report, submission } => {
+                assert!(report.is_none());
+                assert_eq!(submission.id, 2);
+            }
+            other => panic!("refused {:?}", other),
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
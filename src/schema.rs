@@ -0,0 +1,329 @@
+//! Tolerance to changes in the responses of the server
+//!
+//! `User`, `DetailedTask` and `DetailedSubmission` keep the fields they do not know in
+//! their `extra` map, and their fields that are missing from a response take their
+//! default value. The fields most likely to change type, like `DetailedTask::memory_limit`,
+//! also take their default value when the response has a value they cannot hold.
+//!
+//! In lenient mode (`Client::set_lenient`) every difference between a response and the
+//! model parsed from it is reported as a `Drift`, found comparing the response with the
+//! model serialized again.
+
+use crate::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Unknown fields of a response, by name in the models and by path in reports
+pub type Extra = serde_json::Map<String, Value>;
+
+/// deserialize a field of a model, taking its default value if the response has a value
+/// it cannot hold
+pub(crate) fn or_default<'de, D: Deserializer<'de>, T: DeserializeOwned + Default>(
+    d: D,
+) -> Result<T, D::Error> {
+    let value = Value::deserialize(d)?;
+    Ok(T::deserialize(value).unwrap_or_default())
+}
+
+/// Kind of a difference between a response and its model
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftKind {
+    /// field not in the model, kept in `extra` if the model has it
+    Unknown,
+    /// field of the model not in the response, set to its default value
+    Missing,
+    /// value that the model cannot hold, replaced by the default value
+    Mismatch,
+}
+
+/// A difference between a response and its model
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct Drift {
+    /// path of the field, like `scores[0].title`
+    pub path: String,
+    pub kind: DriftKind,
+    /// value in the response, null if missing
+    pub value: Value,
+}
+impl Drift {
+    fn new(path: &[Step], kind: DriftKind, value: &Value) -> Self {
+        Drift {
+            path: display(path),
+            kind,
+            value: value.clone(),
+        }
+    }
+}
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            DriftKind::Unknown => write!(f, "{}: unknown field, value {}", self.path, self.value),
+            DriftKind::Missing => write!(f, "{}: missing field", self.path),
+            DriftKind::Mismatch => write!(f, "{}: unexpected value {}", self.path, self.value),
+        }
+    }
+}
+
+/// A response parsed in lenient mode
+#[derive(Debug, Clone)]
+pub struct Lenient<T> {
+    pub value: T,
+    pub extra: Extra,
+    pub drift: Vec<Drift>,
+}
+
+/// Differences found in a response by a lenient client
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Report {
+    /// api endpoint, like `task`
+    pub endpoint: String,
+    /// action of the request, like `get`
+    pub action: Option<String>,
+    /// name of the model, like `DetailedTask`
+    pub model: String,
+    pub extra: Extra,
+    pub drift: Vec<Drift>,
+}
+
+/// Step of the path to a field
+enum Step {
+    Field(String),
+    Index(usize),
+}
+
+/// get the path to a field as shown in reports, like `scores[0].title`
+fn display(path: &[Step]) -> String {
+    let mut res = String::new();
+    for s in path {
+        match s {
+            Step::Field(f) => {
+                if !res.is_empty() {
+                    res.push('.');
+                }
+                res.push_str(f);
+            }
+            Step::Index(i) => res.push_str(&format!("[{}]", i)),
+        }
+    }
+    res
+}
+
+/// compare a response with the model parsed from it, serialized again
+fn compare(
+    path: &mut Vec<Step>,
+    raw: &Value,
+    parsed: &Value,
+    extra: &mut Extra,
+    drift: &mut Vec<Drift>,
+) {
+    match (raw, parsed) {
+        (Value::Object(r), Value::Object(p)) => {
+            for (k, v) in r {
+                path.push(Step::Field(k.clone()));
+                match p.get(k) {
+                    Some(pv) => compare(path, v, pv, extra, drift),
+                    None => {
+                        extra.insert(display(path), v.clone());
+                        drift.push(Drift::new(path, DriftKind::Unknown, v));
+                    }
+                }
+                path.pop();
+            }
+            for (k, v) in p {
+                if !r.contains_key(k) && !v.is_null() {
+                    path.push(Step::Field(k.clone()));
+                    drift.push(Drift::new(path, DriftKind::Missing, &Value::Null));
+                    path.pop();
+                }
+            }
+        }
+        (Value::Array(r), Value::Array(p)) if r.len() == p.len() => {
+            for (i, (v, pv)) in r.iter().zip(p).enumerate() {
+                path.push(Step::Index(i));
+                compare(path, v, pv, extra, drift);
+                path.pop();
+            }
+        }
+        (Value::Number(a), Value::Number(b)) if a.as_f64() == b.as_f64() => {}
+        _ if raw == parsed => {}
+        _ => drift.push(Drift::new(path, DriftKind::Mismatch, raw)),
+    }
+}
+
+/// get the name of a model, like `DetailedTask`
+pub fn model_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// parse a response as `T` and find its differences with the model,
+/// `Err(2)` if the response is not a JSON object or cannot be parsed
+pub fn parse<T: Serialize + DeserializeOwned>(response: &Value) -> Result<Lenient<T>, u8> {
+    if !response.is_object() {
+        return Err(2);
+    }
+    let value = T::deserialize(response).map_err(|_| 2)?;
+    let parsed = serde_json::to_value(&value).map_err(|_| 2)?;
+    let mut res = Lenient {
+        value,
+        extra: Extra::new(),
+        drift: Vec::new(),
+    };
+    compare(
+        &mut Vec::new(),
+        response,
+        &parsed,
+        &mut res.extra,
+        &mut res.drift,
+    );
+    Ok(res)
+}
+
+impl Client {
+    /// report the differences between the responses of the server and the models or not,
+    /// by default the client is not lenient
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.schema_reports = if lenient {
+            Some(Arc::new(Mutex::new(Vec::new())))
+        } else {
+            None
+        };
+    }
+
+    /// get the differences found since the last call, one report for each response
+    /// with differences, empty if the client is not lenient
+    pub fn schema_drift(&self) -> Vec<Report> {
+        self.schema_reports
+            .as_ref()
+            .map(|reports| std::mem::take(&mut *reports.lock().unwrap()))
+            .unwrap_or_default()
+    }
+
+    /// parse a response, adding a report if it has differences with the model
+    pub(crate) fn parse_lenient<T: Serialize + DeserializeOwned>(
+        &self,
+        reports: &Mutex<Vec<Report>>,
+        endpoint: &str,
+        payload: &Value,
+        response: &Value,
+    ) -> Result<T, u8> {
+        let parsed = parse::<T>(response)?;
+        if !parsed.drift.is_empty() {
+            reports.lock().unwrap().push(Report {
+                endpoint: String::from(endpoint),
+                action: payload["action"].as_str().map(String::from),
+                model: String::from(model_name::<T>()),
+                extra: parsed.extra,
+                drift: parsed.drift,
+            });
+        }
+        Ok(parsed.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::{replaying, Interaction};
+    use crate::{DetailedTask, UserList};
+
+    #[test]
+    fn lenient() {
+        let task = serde_json::json!({
+            "time_limit": 1.0,
+            "statements": {"it": "abc", "en": "def"},
+            "name": "ois_luck",
+            "success": 1,
+            "title": "Luck",
+            "submission_format": ["luck.%l"],
+            "memory_limit": 268435456,
+            "task_type": "Batch",
+            "score_multiplier": 1,
+            "id": 7,
+            "tags": [{"name": "greedy", "can_delete": false}, {"name": 3}, "dp"],
+            "attachments": [],
+            "difficulty": 2
+        });
+        let res = parse::<DetailedTask>(&task).unwrap();
+        assert_eq!(res.value.name, "ois_luck");
+        assert_eq!(res.value.memory_limit, None);
+        assert_eq!(res.value.statements.it.as_deref(), Some("abc"));
+        assert!(res.value.tags.is_empty());
+        assert_eq!(res.value.extra["difficulty"], 2);
+        assert_eq!(res.extra["difficulty"], 2);
+        assert_eq!(res.extra["statements.en"], "def");
+        let drift: Vec<(&str, DriftKind)> = res
+            .drift
+            .iter()
+            .map(|d| (d.path.as_str(), d.kind))
+            .collect();
+        assert_eq!(drift.len(), 5);
+        for d in &[
+            ("memory_limit", DriftKind::Mismatch),
+            ("tags", DriftKind::Mismatch),
+            ("help_available", DriftKind::Missing),
+            ("difficulty", DriftKind::Unknown),
+            ("statements.en", DriftKind::Unknown),
+        ] {
+            assert!(drift.contains(d), "{:?} not in {:?}", d, drift);
+        }
+        assert!(parse::<DetailedTask>(&serde_json::json!([1])).is_err());
+
+        // users are kept even if all of them miss a field
+        let list = serde_json::json!({"num": 2, "success": 1, "users": [
+            {"username": "alice", "first_name": "", "last_name": "", "institute": {},
+                "tasks_solved": 1, "access_level": 6, "join_date": 0.0, "score": 100,
+                "global_access_level": 6, "badge": "gold"},
+            {"username": "bob", "first_name": "", "last_name": "", "institute": {"city": 1},
+                "tasks_solved": 1, "access_level": 6, "join_date": 0.0, "score": 50,
+                "global_access_level": 6,
+                "scores": [{"score": 40.0, "name": "ois_luck", "title": "Luck"}]}
+        ]});
+        let res = parse::<UserList>(&list).unwrap();
+        let users = &res.value.users;
+        assert_eq!(users.len(), 2);
+        assert_eq!((users[0].username.as_str(), users[0].score), ("alice", 100));
+        assert!(users[0].scores.is_none());
+        assert_eq!(users[0].extra["badge"], "gold");
+        assert!(users[0].institute.region.is_none());
+        assert_eq!(users[1].scores.as_ref().unwrap()[0].name, "ois_luck");
+        let drift: Vec<(&str, DriftKind)> = res
+            .drift
+            .iter()
+            .map(|d| (d.path.as_str(), d.kind))
+            .collect();
+        assert_eq!(
+            drift,
+            vec![
+                ("users[0].badge", DriftKind::Unknown),
+                ("users[0].mail_hash", DriftKind::Missing),
+                ("users[1].institute.city", DriftKind::Mismatch),
+                ("users[1].mail_hash", DriftKind::Missing),
+            ]
+        );
+
+        // through a client
+        let mut client = replaying(
+            "user",
+            vec![Interaction::new(
+                "task",
+                serde_json::json!({"action":"get","name":"ois_luck"}),
+                200,
+                task.to_string().as_bytes(),
+            )],
+        );
+        assert_eq!(client.get_task("ois_luck").unwrap().extra["difficulty"], 2);
+        assert!(client.schema_drift().is_empty());
+        client.set_lenient(true);
+        assert_eq!(client.get_task("ois_luck").unwrap().id, 7);
+        let reports = client.schema_drift();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].model, "DetailedTask");
+        assert_eq!(reports[0].action.as_deref(), Some("get"));
+        assert_eq!(reports[0].drift.len(), 5);
+        assert!(client.schema_drift().is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+//! Stress testing: compare a solution with a brute force one on random inputs
+//!
+//! The generator is run with a seed as its only argument and must print an input on stdout.
+//! The solution is run under the limits of the task, the brute force one without limits.
+//! The first input on which the outputs differ (or the solution fails) is saved in the build directory.
+//!
+//! Functions here return the same errors as the ones in `runner`.
+
+use crate::runner::{compile, run, same_output, Program, BUILD_DIR};
+use crate::workspace::TaskWorkspace;
+use std::path::{Path, PathBuf};
+
+/// time limit for the generator and the brute force solution, in seconds
+const TOOL_TIME_LIMIT: f64 = 60.0;
+
+/// Options for stress testing
+#[derive(Debug, Clone)]
+pub struct StressOptions {
+    /// maximum number of inputs to try
+    pub iterations: usize,
+    /// seed used to generate the seeds passed to the generator
+    pub seed: u64,
+    /// compare outputs exactly instead of ignoring whitespace differences
+    pub exact: bool,
+    /// used if the task has no time limit, in seconds
+    pub default_time_limit: f64,
+}
+impl Default for StressOptions {
+    fn default() -> Self {
+        StressOptions {
+            iterations: 1000,
+            seed: crate::now() as u64,
+            exact: false,
+            default_time_limit: 1.0,
+        }
+    }
+}
+
+/// A failing input found by stress testing
+#[derive(Debug, Clone)]
+pub struct StressFailure {
+    /// seed passed to the generator
+    pub seed: u64,
+    /// 0-based index of the failing iteration
+    pub iteration: usize,
+    /// judge-like description of the failure
+    pub reason: String,
+    pub input: PathBuf,
+    /// output of the brute force solution
+    pub expected: PathBuf,
+    /// output of the solution
+    pub got: PathBuf,
+}
+
+/// Result of stress testing
+#[derive(Debug, Clone)]
+pub enum StressOutcome {
+    /// no failing input was found in the given number of iterations
+    Passed(usize),
+    Failed(StressFailure),
+    /// a program did not compile, with its name and the compiler stderr
+    CompilationFailed(String, String),
+}
+
+/// stress test already compiled programs, using `dir` for inputs and outputs
+pub fn stress_programs(
+    dir: &Path,
+    generator: &Program,
+    brute: &Program,
+    solution: &Program,
+    time_limit: f64,
+    memory_limit: Option<u16>,
+    options: &StressOptions,
+) -> Result<StressOutcome, u8> {
+    std::fs::create_dir_all(dir).map_err(|_| 1)?;
+    let empty = dir.join("empty.txt");
+    let input = dir.join("stress_input.txt");
+    let expected = dir.join("stress_expected.txt");
+    let got = dir.join("stress_got.txt");
+    std::fs::write(&empty, "").map_err(|_| 1)?;
+    let mut state = options.seed;
+    for iteration in 0..options.iterations {
+        let seed = crate::splitmix64(&mut state);
+        let mut gen = generator.clone();
+        gen.command.push(seed.to_string());
+        let failure = |reason: String| {
+            Ok(StressOutcome::Failed(StressFailure {
+                seed,
+                iteration,
+                reason,
+                input: input.clone(),
+                expected: expected.clone(),
+                got: got.clone(),
+            }))
+        };
+        if let Some(f) = run(&gen, &empty, &input, TOOL_TIME_LIMIT, None)?.failure {
+            return failure(format!("generator failed: {}", f));
+        }
+        if let Some(f) = run(brute, &input, &expected, TOOL_TIME_LIMIT, None)?.failure {
+            return failure(format!("brute force solution failed: {}", f));
+        }
+        if let Some(f) = run(solution, &input, &got, time_limit, memory_limit)?.failure {
+            return failure(f);
+        }
+        let e = std::fs::read_to_string(&expected).map_err(|_| 1)?;
+        let g = std::fs::read_to_string(&got).unwrap_or_default();
+        if !same_output(&e, &g, options.exact) {
+            return failure(String::from("Output isn't correct"));
+        }
+    }
+    Ok(StressOutcome::Passed(options.iterations))
+}
+
+impl TaskWorkspace {
+    /// compile generator, brute force solution and solution and stress test them
+    /// under the limits of the task
+    pub fn stress(
+        &self,
+        generator: &Path,
+        brute: &Path,
+        solution: &Path,
+        options: &StressOptions,
+    ) -> Result<StressOutcome, u8> {
+        let build = self.dir.join(BUILD_DIR);
+        let mut programs = Vec::new();
+        for (name, source) in &[
+            ("generator", generator),
+            ("brute", brute),
+            ("solution", solution),
+        ] {
+            let c = compile(source, &build.join("stress").join(name))?;
+            match c.program {
+                Some(p) => programs.push(p),
+                None => {
+                    return Ok(StressOutcome::CompilationFailed(
+                        String::from(*name),
+                        c.stderr,
+                    ))
+                }
+            }
+        }
+        stress_programs(
+            &build,
+            &programs[0],
+            &programs[1],
+            &programs[2],
+            self.metadata
+                .time_limit
+                .unwrap_or(options.default_time_limit),
+            self.metadata.memory_limit,
+            options,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Program {
+        Program {
+            command: vec![String::from("sh"), String::from("-c"), String::from(script)],
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finds_failure() {
+        let dir = std::env::temp_dir().join(format!("cms_tools_stress_{}", std::process::id()));
+        // prints the last digit of the seed
+        let generator = sh("echo ${0#${0%?}}");
+        let brute = sh("cat");
+        let wrong = sh("read x; if [ $x -eq 3 ]; then echo 4; else echo $x; fi");
+        let options = StressOptions {
+            iterations: 200,
+            seed: 42,
+            ..Default::default()
+        };
+        match stress_programs(&dir, &generator, &brute, &wrong, 1.0, None, &options).unwrap() {
+            StressOutcome::Failed(f) => {
+                assert_eq!(f.reason, "Output isn't correct");
+                assert_eq!(std::fs::read_to_string(&f.input).unwrap().trim(), "3");
+                assert_eq!(f.seed % 10, 3);
+            }
+            o => panic!("unexpected outcome {:?}", o),
+        }
+        let options = StressOptions {
+            iterations: 5,
+            ..options
+        };
+        match stress_programs(&dir, &generator, &brute, &brute, 1.0, None, &options).unwrap() {
+            StressOutcome::Passed(5) => {}
+            o => panic!("unexpected outcome {:?}", o),
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,351 @@
+//! Training plans: named sets of tasks and progress of a group of users on them
+//!
+//! A plan can be written in TOML
+//! ```toml
+//! [[sets]]
+//! name = "week 1"
+//! deadline = "2020-03-01"
+//! tasks = ["ois_luck", "ois_cake"]
+//!
+//! [[sets]]
+//! name = "dp"
+//! required_score = 50
+//! tasks = ["tai_mle"]
+//! ```
+//! or in the equivalent JSON.
+//!
+//! Functions loading plans return:
+//! * `Err(1)` if the file cannot be read
+//! * `Err(2)` if the file cannot be parsed
+
+use crate::{Client, User};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+fn default_required_score() -> f64 {
+    100.0
+}
+
+/// Named set of tasks
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TaskSet {
+    pub name: String,
+    /// date in `YYYY-MM-DD` format
+    pub deadline: Option<String>,
+    /// score needed on a task to consider it done, 100 by default
+    #[serde(default = "default_required_score")]
+    pub required_score: f64,
+    /// task names
+    pub tasks: Vec<String>,
+}
+impl TaskSet {
+    /// get the deadline as a unix timestamp, the end of the deadline day in UTC
+    ///
+    /// `None` if there is no deadline or it is not a valid date
+    pub fn deadline_timestamp(&self) -> Option<f64> {
+        let deadline = self.deadline.as_ref()?;
+        let parts: Vec<&str> = deadline.trim().split('-').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+        let y: i64 = parts[0].parse().ok()?;
+        let m: i64 = parts[1].parse().ok()?;
+        let d: i64 = parts[2].parse().ok()?;
+        if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+            return None;
+        }
+        let days = crate::date::days_from_civil(y, m, d);
+        Some(((days + 1) * 86400) as f64)
+    }
+}
+
+/// A training plan, a list of task sets
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TrainingPlan {
+    pub sets: Vec<TaskSet>,
+}
+impl TrainingPlan {
+    /// parse a plan in TOML format
+    pub fn from_toml(s: &str) -> Result<Self, u8> {
+        toml::from_str(s).map_err(|_| 2)
+    }
+    /// parse a plan in JSON format
+    pub fn from_json(s: &str) -> Result<Self, u8> {
+        serde_json::from_str(s).map_err(|_| 2)
+    }
+    /// load a plan from a file, it is parsed as JSON if the extension is `json` and as TOML otherwise
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, u8> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).map_err(|_| 1)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json(&s),
+            _ => Self::from_toml(&s),
+        }
+    }
+}
+
+/// Progress of a group of users on a task set
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SetProgress {
+    pub name: String,
+    pub deadline: Option<String>,
+    pub required_score: f64,
+    pub tasks: Vec<String>,
+    /// `scores[i][j]` is the score of the i-th user on the j-th task
+    pub scores: Vec<Vec<f64>>,
+    /// number of tasks done by each user
+    pub done: Vec<usize>,
+    /// whether each user missed the deadline
+    pub overdue: Vec<bool>,
+}
+impl SetProgress {
+    /// whether the i-th user completed the set
+    pub fn completed(&self, i: usize) -> bool {
+        self.done[i] == self.tasks.len()
+    }
+}
+
+/// Progress of a group of users on a training plan
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Progress {
+    pub usernames: Vec<String>,
+    pub sets: Vec<SetProgress>,
+}
+impl Progress {
+    /// compute the progress of users on plan, `now` is the current unix timestamp
+    ///
+    /// users need to have `scores`, which is the case for the ones returned by `get_user`
+    pub fn new(plan: &TrainingPlan, users: &[User], now: f64) -> Self {
+        let user_scores: Vec<HashMap<&str, f64>> = users
+            .iter()
+            .map(|u| {
+                u.scores
+                    .iter()
+                    .flatten()
+                    .map(|s| (s.name.as_str(), s.score))
+                    .collect()
+            })
+            .collect();
+        let sets = plan
+            .sets
+            .iter()
+            .map(|set| {
+                let scores: Vec<Vec<f64>> = user_scores
+                    .iter()
+                    .map(|us| {
+                        set.tasks
+                            .iter()
+                            .map(|t| *us.get(t.as_str()).unwrap_or(&0.0))
+                            .collect()
+                    })
+                    .collect();
+                let done: Vec<usize> = scores
+                    .iter()
+                    .map(|s| s.iter().filter(|x| **x >= set.required_score).count())
+                    .collect();
+                let past_deadline = set.deadline_timestamp().is_some_and(|d| now > d);
+                let overdue = done
+                    .iter()
+                    .map(|d| past_deadline && *d < set.tasks.len())
+                    .collect();
+                SetProgress {
+                    name: set.name.clone(),
+                    deadline: set.deadline.clone(),
+                    required_score: set.required_score,
+                    tasks: set.tasks.clone(),
+                    scores,
+                    done,
+                    overdue,
+                }
+            })
+            .collect();
+        Progress {
+            usernames: users.iter().map(|u| u.username.clone()).collect(),
+            sets,
+        }
+    }
+
+    fn cell(set: &SetProgress, score: f64) -> String {
+        if score >= set.required_score {
+            format!("✓ {}", score)
+        } else {
+            format!("{}", score)
+        }
+    }
+
+    fn summary(set: &SetProgress, i: usize) -> String {
+        let mut s = format!("{}/{}", set.done[i], set.tasks.len());
+        if set.overdue[i] {
+            s.push_str(" (overdue)");
+        }
+        s
+    }
+
+    /// render the progress matrix as Markdown, one table per task set
+    pub fn to_markdown(&self) -> String {
+        let mut res = String::new();
+        for set in &self.sets {
+            write!(res, "## {}", set.name).unwrap();
+            if let Some(deadline) = &set.deadline {
+                write!(res, " (deadline {})", deadline).unwrap();
+            }
+            res.push_str("\n\n| user |");
+            for t in &set.tasks {
+                write!(res, " {} |", t).unwrap();
+            }
+            res.push_str(" done |\n|---|");
+            for _ in &set.tasks {
+                res.push_str("---|");
+            }
+            res.push_str("---|\n");
+            for (i, username) in self.usernames.iter().enumerate() {
+                write!(res, "| {} |", username).unwrap();
+                for score in &set.scores[i] {
+                    write!(res, " {} |", Self::cell(set, *score)).unwrap();
+                }
+                writeln!(res, " {} |", Self::summary(set, i)).unwrap();
+            }
+            res.push('\n');
+        }
+        res
+    }
+
+    /// render the progress matrix as CSV, one row per user and task
+    pub fn to_csv(&self) -> String {
+        fn field(s: &str) -> String {
+            if s.contains([',', '"', '\n']) {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            } else {
+                String::from(s)
+            }
+        }
+        let mut res = String::from("set,user,task,score,done,overdue\n");
+        for set in &self.sets {
+            for (i, username) in self.usernames.iter().enumerate() {
+                for (j, task) in set.tasks.iter().enumerate() {
+                    let score = set.scores[i][j];
+                    writeln!(
+                        res,
+                        "{},{},{},{},{},{}",
+                        field(&set.name),
+                        field(username),
+                        field(task),
+                        score,
+                        score >= set.required_score,
+                        set.overdue[i]
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        res
+    }
+
+    /// render the progress matrix as a standalone HTML page, one table per task set
+    pub fn to_html(&self) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        }
+        let mut res = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Training progress</title>\n\
+             <style>td.done{background:#c8e6c9}td.partial{background:#fff9c4}td.overdue{color:#c62828}</style>\n\
+             </head>\n<body>\n",
+        );
+        for set in &self.sets {
+            write!(res, "<h2>{}", escape(&set.name)).unwrap();
+            if let Some(deadline) = &set.deadline {
+                write!(res, " (deadline {})", escape(deadline)).unwrap();
+            }
+            res.push_str("</h2>\n<table>\n<tr><th>user</th>");
+            for t in &set.tasks {
+                write!(res, "<th>{}</th>", escape(t)).unwrap();
+            }
+            res.push_str("<th>done</th></tr>\n");
+            for (i, username) in self.usernames.iter().enumerate() {
+                write!(res, "<tr><td>{}</td>", escape(username)).unwrap();
+                for score in &set.scores[i] {
+                    let class = if *score >= set.required_score {
+                        "done"
+                    } else if *score > 0.0 {
+                        "partial"
+                    } else {
+                        ""
+                    };
+                    write!(res, "<td class=\"{}\">{}</td>", class, score).unwrap();
+                }
+                let class = if set.overdue[i] { "overdue" } else { "" };
+                writeln!(
+                    res,
+                    "<td class=\"{}\">{}</td></tr>",
+                    class,
+                    escape(&Self::summary(set, i))
+                )
+                .unwrap();
+            }
+            res.push_str("</table>\n");
+        }
+        res.push_str("</body>\n</html>\n");
+        res
+    }
+}
+
+impl Client {
+    /// get the progress of the given users on a training plan
+    pub fn training_progress(
+        &self,
+        plan: &TrainingPlan,
+        usernames: &[&str],
+    ) -> Result<Progress, u8> {
+        let mut users = Vec::new();
+        for username in usernames {
+            users.push(self.get_user(username)?);
+        }
+        Ok(Progress::new(plan, &users, crate::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::tests::user;
+
+    #[test]
+    fn progress() {
+        let plan = TrainingPlan::from_toml(
+            r#"
+            [[sets]]
+            name = "week 1"
+            deadline = "2020-03-01"
+            tasks = ["a", "b"]
+
+            [[sets]]
+            name = "partial"
+            required_score = 50
+            tasks = ["c"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(plan.sets[0].required_score, 100.0);
+        assert_eq!(plan.sets[0].deadline_timestamp(), Some(1583107200.0));
+
+        let users = [
+            user("x", &[("a", 100.0), ("b", 100.0), ("c", 30.0)]),
+            user("y", &[("a", 40.0), ("c", 60.0)]),
+        ];
+        let progress = Progress::new(&plan, &users, 1600000000.0);
+        assert!(progress.sets[0].completed(0));
+        assert_eq!(progress.sets[0].done, vec![2, 0]);
+        assert_eq!(progress.sets[0].overdue, vec![false, true]);
+        assert_eq!(progress.sets[1].done, vec![0, 1]);
+
+        let md = progress.to_markdown();
+        assert!(md.contains("| y | 40 | 0 | 0/2 (overdue) |"));
+        let csv = progress.to_csv();
+        assert!(csv.contains("partial,y,c,60,true,false"));
+        let html = progress.to_html();
+        assert!(html.contains("<td class=\"partial\">40</td>"));
+    }
+}
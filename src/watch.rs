@@ -0,0 +1,589 @@
+//! Watching the site for changes and notifying subscribers
+//!
+//! A `Watcher` polls the newest tasks, the tracked users, the ranking and, on the watched
+//! tasks, the best times and the submissions of the logged user. The new state is compared
+//! with the previous one and the differences are sent as `Event`s to the subscribers:
+//! callbacks, channels and webhooks, which get each event POSTed as JSON.
+//!
+//! Webhooks are delivered in order by a thread per webhook, so a slow endpoint does not
+//! block the polling; a failed delivery is retried a few times before giving up.
+//!
+//! Nothing is compared the first time something is seen, so the first poll only records
+//! the state of the site. Parts whose requests fail keep their previous state.
+
+use crate::query::{self, Outcome};
+use crate::{Client, Stat, Submission, Task, User};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+/// number of times the delivery of an event to a webhook is tried
+const WEBHOOK_ATTEMPTS: u32 = 3;
+/// timeout of a single webhook request
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A change on the site
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum Event {
+    NewTask {
+        name: String,
+        title: String,
+    },
+    /// a tracked user got the full score on a task
+    TaskSolved {
+        username: String,
+        task: String,
+        title: String,
+    },
+    /// the total score of a tracked user changed
+    ScoreChanged {
+        username: String,
+        old: u32,
+        new: u32,
+    },
+    RankChanged {
+        username: String,
+        old: usize,
+        new: usize,
+    },
+    /// a submission of the logged user finished compiling or being evaluated
+    SubmissionEvaluated {
+        task: String,
+        id: usize,
+        outcome: Outcome,
+        score: Option<f64>,
+    },
+    /// the best time on a watched task improved
+    NewFastestTime {
+        task: String,
+        username: String,
+        time: f64,
+        /// previous best time, if any
+        previous: Option<f64>,
+    },
+}
+
+/// Options for watching the site
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// usernames of the tracked users
+    pub users: Vec<String>,
+    /// names of the tasks whose best times and submissions of the logged user are watched
+    pub tasks: Vec<String>,
+    /// number of newest tasks checked for new tasks, 0 to not watch them
+    pub newest_tasks: usize,
+    /// positions of the ranking searched for the ranks of the users, 0 to not watch ranks
+    pub rank_depth: usize,
+    /// time between two polls in `Watcher::run`
+    pub interval: Duration,
+}
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            users: Vec::new(),
+            tasks: Vec::new(),
+            newest_tasks: 20,
+            rank_depth: 1000,
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// What a watcher knows of the site, missing parts have not been seen yet
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct WatchState {
+    /// newest tasks
+    pub tasks: Option<Vec<Task>>,
+    pub users: BTreeMap<String, User>,
+    /// ranks of the users found in the first `rank_depth` positions
+    pub ranks: Option<BTreeMap<String, usize>>,
+    /// submissions of the logged user on each watched task
+    pub submissions: BTreeMap<String, Vec<Submission>>,
+    /// best time on each watched task
+    pub fastest: BTreeMap<String, Option<Stat>>,
+}
+impl WatchState {
+    /// save the state as JSON
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), u8> {
+        let s = serde_json::to_string(self).map_err(|_| 2)?;
+        std::fs::write(path, s).map_err(|_| 1)
+    }
+    /// load a state saved with `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, u8> {
+        let s = std::fs::read_to_string(path).map_err(|_| 1)?;
+        serde_json::from_str(&s).map_err(|_| 2)
+    }
+}
+
+fn new_tasks(old: &[Task], new: &[Task], events: &mut Vec<Event>) {
+    for t in new.iter().filter(|t| old.iter().all(|o| o.name != t.name)) {
+        events.push(Event::NewTask {
+            name: t.name.clone(),
+            title: t.title.clone(),
+        });
+    }
+}
+
+fn user_changes(old: &User, new: &User, events: &mut Vec<Event>) {
+    if old.score != new.score {
+        events.push(Event::ScoreChanged {
+            username: new.username.clone(),
+            old: old.score,
+            new: new.score,
+        });
+    }
+    let old_score = |task: &str| {
+        old.scores
+            .iter()
+            .flatten()
+            .find(|s| s.name == task)
+            .map_or(0.0, |s| s.score)
+    };
+    for s in new.scores.iter().flatten() {
+        if s.score >= 100.0 && old_score(&s.name) < 100.0 {
+            events.push(Event::TaskSolved {
+                username: new.username.clone(),
+                task: s.name.clone(),
+                title: s.title.clone(),
+            });
+        }
+    }
+}
+
+fn evaluated_submissions(
+    task: &str,
+    old: &[Submission],
+    new: &[Submission],
+    events: &mut Vec<Event>,
+) {
+    let old: HashMap<usize, Outcome> = old.iter().map(|s| (s.id, query::outcome(s))).collect();
+    let mut new: Vec<&Submission> = new.iter().collect();
+    new.sort_by_key(|s| s.id);
+    for sub in new {
+        let outcome = query::outcome(sub);
+        let was_pending = old.get(&sub.id).is_none_or(|o| *o == Outcome::Pending);
+        if outcome != Outcome::Pending && was_pending {
+            events.push(Event::SubmissionEvaluated {
+                task: String::from(task),
+                id: sub.id,
+                outcome,
+                score: sub.score,
+            });
+        }
+    }
+}
+
+/// get the events between two states of the site
+pub fn diff(old: &WatchState, new: &WatchState) -> Vec<Event> {
+    let mut events = Vec::new();
+    if let (Some(old), Some(new)) = (&old.tasks, &new.tasks) {
+        new_tasks(old, new, &mut events);
+    }
+    for (username, user) in &new.users {
+        if let Some(old) = old.users.get(username) {
+            user_changes(old, user, &mut events);
+        }
+    }
+    if let (Some(old), Some(new)) = (&old.ranks, &new.ranks) {
+        for (username, rank) in new {
+            match old.get(username) {
+                Some(old) if old != rank => events.push(Event::RankChanged {
+                    username: username.clone(),
+                    old: *old,
+                    new: *rank,
+                }),
+                _ => {}
+            }
+        }
+    }
+    for (task, subs) in &new.submissions {
+        if let Some(old) = old.submissions.get(task) {
+            evaluated_submissions(task, old, subs, &mut events);
+        }
+    }
+    for (task, best) in &new.fastest {
+        if let (Some(old), Some(best)) = (old.fastest.get(task), best) {
+            let previous = old.as_ref().map(|s| s.time);
+            if previous.is_none_or(|t| best.time < t) {
+                events.push(Event::NewFastestTime {
+                    task: task.clone(),
+                    username: best.username.clone(),
+                    time: best.time,
+                    previous,
+                });
+            }
+        }
+    }
+    events
+}
+
+/// Where events are sent
+enum Subscriber {
+    Callback(Box<dyn Fn(&Event) + Send>),
+    Channel(Sender<Event>),
+    /// queue of the thread POSTing the events to a webhook
+    Webhook(Sender<Event>),
+}
+
+/// Polls the site and sends the changes to its subscribers
+pub struct Watcher {
+    client: Client,
+    options: WatchOptions,
+    state: WatchState,
+    subscribers: Vec<Subscriber>,
+}
+impl Watcher {
+    /// watch the site with `client`, which must be logged to watch submissions
+    pub fn new(client: Client, options: WatchOptions) -> Self {
+        Watcher {
+            client,
+            options,
+            state: WatchState::default(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn state(&self) -> &WatchState {
+        &self.state
+    }
+
+    /// start from a saved state, to get the changes since it was saved
+    pub fn set_state(&mut self, state: WatchState) {
+        self.state = state;
+    }
+
+    /// call `callback` for every event
+    pub fn subscribe<F: Fn(&Event) + Send + 'static>(&mut self, callback: F) {
+        self.subscribers
+            .push(Subscriber::Callback(Box::new(callback)));
+    }
+
+    /// get a channel receiving every event
+    pub fn channel(&mut self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(Subscriber::Channel(sender));
+        receiver
+    }
+
+    /// POST every event as JSON to `url`, from a background thread which stops when
+    /// the watcher is dropped and the queued events are delivered
+    pub fn webhook(&mut self, url: &str) {
+        let (sender, receiver) = mpsc::channel();
+        let url = String::from(url);
+        std::thread::spawn(move || post_events(&url, receiver));
+        self.subscribers.push(Subscriber::Webhook(sender));
+    }
+
+    /// get the current state of the site, keeping the previous state of the parts
+    /// whose requests fail
+    fn fetch(&self) -> WatchState {
+        let client = &self.client;
+        let options = &self.options;
+        let mut state = self.state.clone();
+        if options.newest_tasks > 0 {
+            if let Ok(list) = client.get_task_list(0, options.newest_tasks, "newest", None, None) {
+                state.tasks = Some(list.tasks);
+            }
+        }
+        for username in &options.users {
+            if let Ok(user) = client.get_user(username) {
+                state.users.insert(username.clone(), user);
+            }
+        }
+        if options.rank_depth > 0 && !options.users.is_empty() {
+            if let Ok(snapshot) = client.ranking_snapshot(0, options.rank_depth) {
+                state.ranks = Some(
+                    snapshot
+                        .entries
+                        .into_iter()
+                        .filter(|e| options.users.contains(&e.username))
+                        .map(|e| (e.username, e.rank))
+                        .collect(),
+                );
+            }
+        }
+        for task in &options.tasks {
+            if client.logged {
+                if let Ok(list) = client.get_submission_list(task) {
+                    state.submissions.insert(task.clone(), list.submissions);
+                }
+            }
+            if let Ok(stats) = client.get_stats(task) {
+                state
+                    .fastest
+                    .insert(task.clone(), stats.best.into_iter().next());
+            }
+        }
+        state
+    }
+
+    fn deliver(&self, event: &Event) {
+        for subscriber in &self.subscribers {
+            match subscriber {
+                Subscriber::Callback(callback) => callback(event),
+                // the receiver may have been dropped, it just does not get events anymore
+                Subscriber::Channel(sender) => sender.send(event.clone()).unwrap_or(()),
+                // the thread only stops when the sender is dropped
+                Subscriber::Webhook(sender) => sender.send(event.clone()).unwrap_or(()),
+            }
+        }
+    }
+
+    /// poll the site once, send the changes to the subscribers and return them
+    pub fn poll(&mut self) -> Vec<Event> {
+        let state = self.fetch();
+        let events = diff(&self.state, &state);
+        self.state = state;
+        for event in &events {
+            self.deliver(event);
+        }
+        events
+    }
+
+    /// poll the site every `WatchOptions::interval`, forever
+    pub fn run(&mut self) {
+        loop {
+            self.poll();
+            std::thread::sleep(self.options.interval);
+        }
+    }
+}
+
+/// POST the events received from `events` to `url`, retrying the failed deliveries
+fn post_events(url: &str, events: Receiver<Event>) {
+    let http = match reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build() {
+        Ok(http) => http,
+        Err(err) => {
+            tracing::warn!(url, %err, "cannot create the webhook client");
+            return;
+        }
+    };
+    for event in events {
+        let delivered = (0..WEBHOOK_ATTEMPTS).any(|attempt| {
+            if attempt > 0 {
+                std::thread::sleep(Duration::from_secs(1 << attempt));
+            }
+            let res = http.post(url).json(&event).send();
+            res.is_ok_and(|r| r.status().is_success())
+        });
+        if !delivered {
+            tracing::warn!(url, ?event, "cannot deliver event");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::{replaying, user_response, Interaction};
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// receive a webhook request for each of `statuses`, answering it with the status,
+    /// and return their bodies
+    fn receive(listener: TcpListener, statuses: &[u16]) -> Vec<String> {
+        let mut bodies = Vec::new();
+        for (status, stream) in statuses.iter().zip(listener.incoming()) {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(l) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = l.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            bodies.push(String::from_utf8(body).unwrap());
+            write!(
+                stream,
+                "HTTP/1.1 {} -\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+        }
+        bodies
+    }
+
+    #[test]
+    fn watch() {
+        let task = |name: &str| {
+            json!({"score": null, "title": name.to_uppercase(),
+            "score_multiplier": 1.0, "id": 1, "name": name})
+        };
+        let user = |username: &str, score: u32, luck: f64| {
+            user_response(username, score, &[("ois_luck", luck)])
+        };
+        let submission = |id: usize, compilation: Option<&str>, score: Option<f64>| {
+            json!({"files": [], "compilation_outcome": compilation, "task_id": 1,
+                "timestamp": 0.0, "evaluation_outcome": null, "score": score, "id": id})
+        };
+        let stats = |username: &str, time: f64| {
+            json!({"success": 1, "nsubs": 10, "nusers": 4, "nsubscorrect": 3,
+                "nuserscorrect": 2, "best": [{"username": username, "time": time}]})
+        };
+        let interaction =
+            |endpoint: &str, request: serde_json::Value, response: serde_json::Value| {
+                Interaction::new(endpoint, request, 200, response.to_string().as_bytes())
+            };
+        let tasks_request = json!({"action":"list","first":0,"last":10,"order":"newest",
+            "tag":null,"search":null});
+        let user_request = json!({"action":"get","username":"alice"});
+        let ranking_request = json!({"action":"list","first":0,"last":100});
+        let submissions_request = json!({"action":"list","task_name":"ois_luck"});
+        let stats_request = json!({"action":"stats","name":"ois_luck"});
+        let mut interactions = Vec::new();
+        for poll in 0..2 {
+            let tasks = if poll == 0 {
+                vec![task("ois_a")]
+            } else {
+                vec![task("ois_b"), task("ois_a")]
+            };
+            let (score, luck) = if poll == 0 { (100, 40.0) } else { (200, 100.0) };
+            let mut ranking = json!({"num": 2, "success": 1});
+            ranking["users"] = if poll == 0 {
+                json!([user("carol", 300, 100.0), user("alice", score, luck)])
+            } else {
+                json!([user("alice", score, luck), user("carol", 300, 100.0)])
+            };
+            let subs = if poll == 0 {
+                vec![submission(5, None, None)]
+            } else {
+                vec![
+                    submission(6, Some("fail"), None),
+                    submission(5, Some("ok"), Some(100.0)),
+                ]
+            };
+            let best = if poll == 0 {
+                ("carol", 0.5)
+            } else {
+                ("alice", 0.25)
+            };
+            interactions.extend(vec![
+                interaction(
+                    "task",
+                    tasks_request.clone(),
+                    json!({"tasks": tasks, "num": 2, "success": 1}),
+                ),
+                interaction("user", user_request.clone(), user("alice", score, luck)),
+                interaction("user", ranking_request.clone(), ranking),
+                interaction(
+                    "submission",
+                    submissions_request.clone(),
+                    json!({"submissions": subs, "success": 1}),
+                ),
+                interaction("task", stats_request.clone(), stats(best.0, best.1)),
+            ]);
+        }
+        let mut client = replaying("alice", interactions);
+        client.logged = true;
+
+        let mut watcher = Watcher::new(
+            client,
+            WatchOptions {
+                users: vec![String::from("alice")],
+                tasks: vec![String::from("ois_luck")],
+                newest_tasks: 10,
+                rank_depth: 100,
+                ..Default::default()
+            },
+        );
+        let called = Arc::new(Mutex::new(0));
+        let counter = called.clone();
+        watcher.subscribe(move |_| *counter.lock().unwrap() += 1);
+        let receiver = watcher.channel();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        watcher.webhook(&format!("http://{}/hook", listener.local_addr().unwrap()));
+        let server = std::thread::spawn(move || receive(listener, &[200; 7]));
+
+        assert!(watcher.poll().is_empty());
+        assert_eq!(watcher.state().ranks.as_ref().unwrap()["alice"], 2);
+        let events = watcher.poll();
+        let expected = vec![
+            Event::NewTask {
+                name: String::from("ois_b"),
+                title: String::from("OIS_B"),
+            },
+            Event::ScoreChanged {
+                username: String::from("alice"),
+                old: 100,
+                new: 200,
+            },
+            Event::TaskSolved {
+                username: String::from("alice"),
+                task: String::from("ois_luck"),
+                title: String::from("ois_luck"),
+            },
+            Event::RankChanged {
+                username: String::from("alice"),
+                old: 2,
+                new: 1,
+            },
+            Event::SubmissionEvaluated {
+                task: String::from("ois_luck"),
+                id: 5,
+                outcome: Outcome::Full,
+                score: Some(100.0),
+            },
+            Event::SubmissionEvaluated {
+                task: String::from("ois_luck"),
+                id: 6,
+                outcome: Outcome::CompilationFailed,
+                score: None,
+            },
+            Event::NewFastestTime {
+                task: String::from("ois_luck"),
+                username: String::from("alice"),
+                time: 0.25,
+                previous: Some(0.5),
+            },
+        ];
+        assert_eq!(events, expected);
+        assert_eq!(*called.lock().unwrap(), 7);
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), expected);
+        let received: Vec<Event> = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|b| serde_json::from_str(b).unwrap())
+            .collect();
+        assert_eq!(received, expected);
+        assert_eq!(
+            serde_json::to_value(&expected[3]).unwrap(),
+            json!({"type": "RankChanged", "username": "alice", "old": 2, "new": 1})
+        );
+    }
+
+    #[test]
+    fn retries_webhooks() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || receive(listener, &[503, 200]));
+        let (sender, receiver) = mpsc::channel();
+        let event = Event::NewTask {
+            name: String::from("ois_luck"),
+            title: String::from("Luck"),
+        };
+        sender.send(event.clone()).unwrap();
+        drop(sender);
+        post_events(&url, receiver);
+        let received = server.join().unwrap();
+        assert_eq!(received.len(), 2);
+        for body in received {
+            assert_eq!(serde_json::from_str::<Event>(&body).unwrap(), event);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Local workspaces, one directory per task
+//!
+//! A task directory contains:
+//! * `statement.pdf`
+//! * the attachments of the task
+//! * `task.json`, the `TaskMetadata` of the task
+//! * a solution stub for every file in the submission format
+//! * `submissions/`, with the sources of the previous submissions
+//!
+//! Materializing a task again only downloads what changed, and never overwrites solution stubs.
+//!
+//! Functions here return `Err(1)` also if a file cannot be read or written.
+
+use crate::{Client, DetailedTask, File};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// name of the metadata file in a task directory
+pub const METADATA_FILE: &str = "task.json";
+/// name of the statement file in a task directory
+pub const STATEMENT_FILE: &str = "statement.pdf";
+/// name of the directory with previous submissions in a task directory
+pub const SUBMISSIONS_DIR: &str = "submissions";
+
+/// Metadata of a task, saved in the task directory
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TaskMetadata {
+    pub name: String,
+    pub title: String,
+    pub id: usize,
+    /// in seconds
+    pub time_limit: Option<f64>,
+    /// in MiB
+    pub memory_limit: Option<u16>,
+    pub task_type: String,
+    pub submission_format: Vec<String>,
+    pub score_multiplier: f64,
+    pub tags: Vec<String>,
+    /// file names of the attachments in the task directory
+    pub attachments: Vec<String>,
+    /// digests of the downloaded files, by path relative to the task directory
+    pub digests: HashMap<String, String>,
+}
+impl TaskMetadata {
+    /// metadata of a task, with no downloaded files
+    pub fn new(task: &DetailedTask) -> Self {
+        TaskMetadata {
+            name: task.name.clone(),
+            title: task.title.clone(),
+            id: task.id,
+            time_limit: task.time_limit,
+            memory_limit: task.memory_limit,
+            task_type: task.task_type.clone(),
+            submission_format: task.submission_format.clone(),
+            score_multiplier: task.score_multiplier,
+            tags: task.tags.iter().map(|t| t.name.clone()).collect(),
+            attachments: task
+                .attachments
+                .iter()
+                .filter_map(|a| a.first().map(|name| sanitize(name)))
+                .collect(),
+            digests: HashMap::new(),
+        }
+    }
+}
+
+/// Options for `Client::materialize_task`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct WorkspaceOptions {
+    /// extension used for solution stubs
+    pub lang: String,
+    /// download previous submissions, this needs the client to be logged
+    pub submissions: bool,
+}
+impl Default for WorkspaceOptions {
+    fn default() -> Self {
+        WorkspaceOptions {
+            lang: String::from("cpp"),
+            submissions: true,
+        }
+    }
+}
+
+/// A task directory
+#[derive(Debug, Clone)]
+pub struct TaskWorkspace {
+    pub dir: PathBuf,
+    pub metadata: TaskMetadata,
+}
+impl TaskWorkspace {
+    /// open an already materialized task directory
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, u8> {
+        let dir = dir.as_ref().to_path_buf();
+        let s = std::fs::read_to_string(dir.join(METADATA_FILE)).map_err(|_| 1)?;
+        let metadata = serde_json::from_str(&s).map_err(|_| 2)?;
+        Ok(TaskWorkspace { dir, metadata })
+    }
+    /// save the metadata in the task directory
+    pub fn save_metadata(&self) -> Result<(), u8> {
+        let s = serde_json::to_string_pretty(&self.metadata).map_err(|_| 2)?;
+        std::fs::write(self.dir.join(METADATA_FILE), s).map_err(|_| 1)
+    }
+    /// path of the solution file for a submission format entry, like `luck.%l`
+    pub fn solution_path(&self, format: &str, lang: &str) -> PathBuf {
+        self.dir.join(solution_file(format, lang))
+    }
+    /// paths of the attachments in the task directory
+    pub fn attachment_paths(&self) -> Vec<PathBuf> {
+        self.metadata
+            .attachments
+            .iter()
+            .map(|a| self.dir.join(a))
+            .collect()
+    }
+
+    /// write `file` at `path` (relative to the task directory) unless it was already downloaded,
+    /// returns whether it was downloaded
+    fn sync_file(&mut self, client: &Client, path: &str, file: &File) -> Result<bool, u8> {
+        let full = self.dir.join(path);
+        if full.exists() && self.metadata.digests.get(path) == Some(&file.digest) {
+            return Ok(false);
+        }
+        if let Some(parent) = full.parent() {
+            std::fs::create_dir_all(parent).map_err(|_| 1)?;
+        }
+        let data = client.get_file_bytes(file)?;
+        std::fs::write(&full, data).map_err(|_| 1)?;
+        self.metadata
+            .digests
+            .insert(String::from(path), file.digest.clone());
+        Ok(true)
+    }
+}
+
+/// What `Client::materialize_task` did
+#[derive(Debug, Clone)]
+pub struct SyncReport {
+    pub workspace: TaskWorkspace,
+    /// files downloaded, relative to the task directory
+    pub downloaded: Vec<String>,
+    /// solution stubs created, relative to the task directory
+    pub created: Vec<String>,
+}
+
+/// keep only safe characters of a file name coming from the server
+pub(crate) fn sanitize(name: &str) -> String {
+    if name.is_empty() || name == "." || name == ".." {
+        return String::from("_");
+    }
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// name of the solution file for a submission format entry, like `luck.%l`
+fn solution_file(format: &str, lang: &str) -> String {
+    sanitize(&format.replace("%l", lang))
+}
+
+impl Client {
+    /// create or update the directory `root/<task name>` for a task
+    pub fn materialize_task<P: AsRef<Path>>(
+        &self,
+        root: P,
+        name: &str,
+        options: &WorkspaceOptions,
+    ) -> Result<SyncReport, u8> {
+        let task = self.get_task(name)?;
+        let dir = root.as_ref().join(sanitize(&task.name));
+        std::fs::create_dir_all(&dir).map_err(|_| 1)?;
+        let mut metadata = TaskMetadata::new(&task);
+        if let Ok(old) = TaskWorkspace::open(&dir) {
+            metadata.digests = old.metadata.digests;
+        }
+        let mut ws = TaskWorkspace { dir, metadata };
+        let mut downloaded = Vec::new();
+        let mut created = Vec::new();
+
+        if let Some(digest) = &task.statements.it {
+            let file = File {
+                name: String::from("testo.pdf"),
+                digest: digest.clone(),
+            };
+            if ws.sync_file(self, STATEMENT_FILE, &file)? {
+                downloaded.push(String::from(STATEMENT_FILE));
+            }
+        }
+        for attachment in &task.attachments {
+            if let [name, digest, ..] = attachment.as_slice() {
+                let file = File {
+                    name: name.clone(),
+                    digest: digest.clone(),
+                };
+                let path = sanitize(name);
+                if ws.sync_file(self, &path, &file)? {
+                    downloaded.push(path);
+                }
+            }
+        }
+        for format in &task.submission_format {
+            let path = solution_file(format, &options.lang);
+            let full = ws.dir.join(&path);
+            if !full.exists() {
+                std::fs::write(&full, "").map_err(|_| 1)?;
+                created.push(path);
+            }
+        }
+        if options.submissions {
+            for sub in self.get_submission_list(&task.name)?.submissions {
+                for file in &sub.files {
+                    let path = format!("{}/{}_{}", SUBMISSIONS_DIR, sub.id, sanitize(&file.name));
+                    if ws.sync_file(self, &path, file)? {
+                        downloaded.push(path);
+                    }
+                }
+            }
+        }
+        ws.save_metadata()?;
+        Ok(SyncReport {
+            workspace: ws,
+            downloaded,
+            created,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::{replaying, Interaction};
+    use serde_json::json;
+
+    /// response to `get_task` with the given digest of the statement
+    fn task(statement: &str) -> serde_json::Value {
+        json!({
+            "time_limit": 1.0,
+            "help_available": false,
+            "statements": {"it": statement},
+            "name": "ois_luck",
+            "success": 1,
+            "title": "Luck",
+            "submission_format": ["luck.%l"],
+            "memory_limit": 256,
+            "task_type": "Batch",
+            "score_multiplier": 1.5,
+            "id": 42,
+            "tags": [{"name": "dp", "can_delete": false}],
+            "attachments": [["luck.zip", "def"]]
+        })
+    }
+
+    #[test]
+    fn metadata_roundtrip() {
+        let task: DetailedTask = serde_json::from_value(task("abc")).unwrap();
+        let dir = std::env::temp_dir().join(format!("cms_tools_workspace_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ws = TaskWorkspace {
+            dir: dir.clone(),
+            metadata: TaskMetadata::new(&task),
+        };
+        ws.save_metadata().unwrap();
+        let ws = TaskWorkspace::open(&dir).unwrap();
+        assert_eq!(ws.metadata.tags, vec!["dp"]);
+        assert_eq!(ws.metadata.attachments, vec!["luck.zip"]);
+        assert_eq!(ws.solution_path("luck.%l", "cpp"), dir.join("luck.cpp"));
+        assert_eq!(ws.solution_path("../x.%l", "cpp"), dir.join(".._x.cpp"));
+        std::fs::remove_dir_all(dir).unwrap();
+        assert_eq!(sanitize("../a b.cpp"), ".._a_b.cpp");
+        assert_eq!(sanitize(".."), "_");
+    }
+    #[test]
+    fn resync() {
+        let get_task = json!({"action":"get","name":"ois_luck"});
+        let task_response = |statement: &str| {
+            Interaction::new(
+                "task",
+                get_task.clone(),
+                200,
+                task(statement).to_string().as_bytes(),
+            )
+        };
+        let client = replaying(
+            "user",
+            vec![
+                task_response("abc"),
+                task_response("abc"),
+                task_response("xyz"),
+                Interaction::new("files/abc/testo.pdf", serde_json::Value::Null, 200, b"old"),
+                Interaction::new("files/def/luck.zip", serde_json::Value::Null, 200, b"zip"),
+                Interaction::new("files/xyz/testo.pdf", serde_json::Value::Null, 200, b"new"),
+            ],
+        );
+        let root = std::env::temp_dir().join(format!("cms_tools_resync_{}", std::process::id()));
+        let options = WorkspaceOptions {
+            lang: String::from("cpp"),
+            submissions: false,
+        };
+        let report = client
+            .materialize_task(&root, "ois_luck", &options)
+            .unwrap();
+        assert_eq!(report.downloaded, vec![STATEMENT_FILE, "luck.zip"]);
+        assert_eq!(report.created, vec!["luck.cpp"]);
+        let stub = report.workspace.solution_path("luck.%l", "cpp");
+        std::fs::write(&stub, "int main() {}").unwrap();
+
+        // nothing changed
+        let report = client
+            .materialize_task(&root, "ois_luck", &options)
+            .unwrap();
+        assert!(report.downloaded.is_empty());
+        assert!(report.created.is_empty());
+        assert_eq!(std::fs::read_to_string(&stub).unwrap(), "int main() {}");
+
+        // new statement
+        let report = client
+            .materialize_task(&root, "ois_luck", &options)
+            .unwrap();
+        assert_eq!(report.downloaded, vec![STATEMENT_FILE]);
+        let dir = report.workspace.dir;
+        assert_eq!(std::fs::read(dir.join(STATEMENT_FILE)).unwrap(), b"new");
+        assert_eq!(std::fs::read_to_string(&stub).unwrap(), "int main() {}");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}